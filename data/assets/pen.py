@@ -0,0 +1,156 @@
+"""Generates pen.glb: a grip, a tip and a mode ring, each with its own material.
+
+Run from the repository root with `python3 data/assets/pen.py`.
+The pen points along +Y from its tip at the origin, matching `PenInput`.
+"""
+
+import json
+import math
+import struct
+
+LENGTH = 0.075
+RADIUS = 0.0028
+TIP_LENGTH = 0.015
+RING_HEIGHT = 0.071
+RING_RADIUS = 0.004
+RING_THICKNESS = 0.0008
+SEGMENTS = 24
+
+
+def lathe(profile):
+    """Revolves (radius, y) points around Y into a triangle mesh."""
+    positions, normals, indices = [], [], []
+    for i in range(SEGMENTS + 1):
+        angle = i / SEGMENTS * math.tau
+        c, s = math.cos(angle), math.sin(angle)
+        for j, (r, y) in enumerate(profile):
+            # normal from the profile's slope
+            r0, y0 = profile[max(j - 1, 0)]
+            r1, y1 = profile[min(j + 1, len(profile) - 1)]
+            nr, ny = y1 - y0, -(r1 - r0)
+            length = math.hypot(nr, ny) or 1.0
+            positions.append((r * c, y, -r * s))
+            normals.append((nr / length * c, ny / length, -nr / length * s))
+    rows = len(profile)
+    for i in range(SEGMENTS):
+        for j in range(rows - 1):
+            a = i * rows + j
+            b = a + rows
+            indices += [a, b, a + 1, a + 1, b, b + 1]
+    return positions, normals, indices
+
+
+def torus(y, radius, thickness):
+    profile = [
+        (radius + thickness * math.cos(a / SEGMENTS * math.tau),
+         y + thickness * math.sin(a / SEGMENTS * math.tau))
+        for a in range(SEGMENTS + 1)
+    ]
+    return lathe(profile)
+
+
+parts = {
+    "tip": ([(0.0, 0.0), (RADIUS * 0.9, TIP_LENGTH)], (0.9, 0.9, 0.9, 1.0)),
+    "grip": (
+        [
+            (RADIUS * 0.9, TIP_LENGTH),
+            (RADIUS, TIP_LENGTH + 0.002),
+            (RADIUS, LENGTH - 0.002),
+            (RADIUS * 0.6, LENGTH),
+            (0.0, LENGTH),
+        ],
+        (0.3, 0.3, 0.3, 1.0),
+    ),
+    "mode_ring": (None, (1.0, 1.0, 1.0, 1.0)),
+}
+
+buffer = bytearray()
+gltf = {
+    "asset": {"version": "2.0", "generator": "solar-sailer pen.py"},
+    "scene": 0,
+    "scenes": [{"nodes": []}],
+    "nodes": [],
+    "meshes": [],
+    "materials": [],
+    "accessors": [],
+    "bufferViews": [],
+    "buffers": [],
+}
+
+
+def add_view(data, target, components, component_type, count, minmax=None):
+    while len(buffer) % 4:
+        buffer.append(0)
+    gltf["bufferViews"].append(
+        {"buffer": 0, "byteOffset": len(buffer), "byteLength": len(data), "target": target}
+    )
+    buffer.extend(data)
+    accessor = {
+        "bufferView": len(gltf["bufferViews"]) - 1,
+        "componentType": component_type,
+        "count": count,
+        "type": components,
+    }
+    if minmax:
+        accessor["min"], accessor["max"] = minmax
+    gltf["accessors"].append(accessor)
+    return len(gltf["accessors"]) - 1
+
+
+for name, (profile, color) in parts.items():
+    if profile is None:
+        positions, normals, indices = torus(0.0, RING_RADIUS, RING_THICKNESS)
+        translation = [0.0, RING_HEIGHT, 0.0]
+    else:
+        positions, normals, indices = lathe(profile)
+        translation = [0.0, 0.0, 0.0]
+    minmax = (
+        [min(p[i] for p in positions) for i in range(3)],
+        [max(p[i] for p in positions) for i in range(3)],
+    )
+    position = add_view(
+        b"".join(struct.pack("<3f", *p) for p in positions), 34962, "VEC3", 5126, len(positions), minmax
+    )
+    normal = add_view(
+        b"".join(struct.pack("<3f", *n) for n in normals), 34962, "VEC3", 5126, len(normals)
+    )
+    index = add_view(
+        struct.pack(f"<{len(indices)}H", *indices), 34963, "SCALAR", 5123, len(indices)
+    )
+    gltf["materials"].append(
+        {
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": list(color),
+                "metallicFactor": 0.2,
+                "roughnessFactor": 0.5,
+            },
+            "emissiveFactor": [0.0, 0.0, 0.0],
+        }
+    )
+    gltf["meshes"].append(
+        {
+            "name": name,
+            "primitives": [
+                {
+                    "attributes": {"POSITION": position, "NORMAL": normal},
+                    "indices": index,
+                    "material": len(gltf["materials"]) - 1,
+                }
+            ],
+        }
+    )
+    gltf["nodes"].append({"name": name, "mesh": len(gltf["meshes"]) - 1, "translation": translation})
+    gltf["scenes"][0]["nodes"].append(len(gltf["nodes"]) - 1)
+
+while len(buffer) % 4:
+    buffer.append(0)
+gltf["buffers"].append({"byteLength": len(buffer)})
+json_chunk = json.dumps(gltf, separators=(",", ":")).encode()
+json_chunk += b" " * (-len(json_chunk) % 4)
+glb = struct.pack("<4sII", b"glTF", 2, 12 + 8 + len(json_chunk) + 8 + len(buffer))
+glb += struct.pack("<I4s", len(json_chunk), b"JSON") + json_chunk
+glb += struct.pack("<I4s", len(buffer), b"BIN\0") + bytes(buffer)
+
+with open("data/org.stardustxr.SolarSailer/pen.glb", "wb") as file:
+    file.write(glb)
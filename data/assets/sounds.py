@@ -0,0 +1,77 @@
+"""Generates the wind loops and cue sounds.
+
+Run from the repository root with `python3 data/assets/sounds.py`.
+Each wind loop is filtered noise centered on a higher pitch than the last,
+`MotionAudio` switches between them by speed.
+"""
+
+import math
+import random
+import struct
+import wave
+
+RATE = 22050
+OUT = "data/org.stardustxr.SolarSailer/"
+WIND_SECONDS = 2.0
+
+
+def write(name, samples):
+    with wave.open(OUT + name + ".wav", "wb") as file:
+        file.setnchannels(1)
+        file.setsampwidth(2)
+        file.setframerate(RATE)
+        file.writeframes(
+            b"".join(struct.pack("<h", int(max(-1.0, min(1.0, s)) * 32000)) for s in samples)
+        )
+
+
+def wind(cutoff, seed):
+    random.seed(seed)
+    count = int(RATE * WIND_SECONDS)
+    # two one-pole lowpasses over white noise, run twice so the loop seam matches
+    alpha = 1.0 - math.exp(-math.tau * cutoff / RATE)
+    a = b = 0.0
+    out = []
+    noise = [random.uniform(-1.0, 1.0) for _ in range(count)]
+    for _ in range(2):
+        out = []
+        for n in noise:
+            a += alpha * (n - a)
+            b += alpha * (a - b)
+            out.append(b)
+    peak = max(abs(s) for s in out)
+    # slow gusts, a whole number of them per loop so it stays seamless
+    return [
+        s / peak * 0.6 * (0.75 + 0.25 * math.sin(math.tau * 2 * i / count))
+        for i, s in enumerate(out)
+    ]
+
+
+def tone(frequencies, seconds, decay):
+    count = int(RATE * seconds)
+    return [
+        sum(math.sin(math.tau * f * i / RATE) for f in frequencies)
+        / len(frequencies)
+        * math.exp(-decay * i / RATE)
+        * min(1.0, i / (RATE * 0.005))
+        * 0.7
+        for i in range(count)
+    ]
+
+
+def sweep(start, end, seconds):
+    count = int(RATE * seconds)
+    phase = 0.0
+    out = []
+    for i in range(count):
+        t = i / count
+        phase += math.tau * (start + (end - start) * t) / RATE
+        out.append(math.sin(phase) * (1.0 - t) * min(1.0, i / (RATE * 0.005)) * 0.6)
+    return out
+
+
+for layer, cutoff in enumerate([250.0, 600.0, 1400.0]):
+    write(f"wind_{layer}", wind(cutoff, layer))
+write("mode_switch", tone([660.0, 990.0], 0.25, 14.0))
+write("brake", sweep(500.0, 120.0, 0.3))
+write("limit", tone([180.0, 190.0], 0.35, 8.0))
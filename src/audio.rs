@@ -0,0 +1,115 @@
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use stardust_xr_fusion::{
+	ClientHandle,
+	audio::{Sound, SoundAspect as _},
+	node::NodeResult,
+	objects::hmd,
+	spatial::{SpatialAspect as _, Transform},
+	values::ResourceID,
+};
+
+use crate::{APP_ID, bounds::Edge};
+
+/// Wind that gets louder and higher as the user speeds up, and cues for discrete events.
+///
+/// Sounds have no volume or pitch controls, so the wind source is moved closer to
+/// the head to get louder, and loops recorded at higher pitches take over as speed rises.
+pub struct MotionAudio {
+	wind: Vec<Sound>,
+	/// Wind layer currently looping and when it was last started.
+	playing: Option<(usize, Instant)>,
+	mode_switch: Sound,
+	brake: Sound,
+	limit: Sound,
+	at_limit: bool,
+}
+
+impl MotionAudio {
+	/// Length of the wind loops in `data/`.
+	const WIND_LOOP: Duration = Duration::from_secs(2);
+	/// Speeds in m/s each wind layer after the first takes over at.
+	const WIND_LAYER_SPEEDS: [f32; 2] = [2.0, 8.0];
+	/// Slower than this is silent.
+	const MIN_SPEED: f32 = 0.1;
+	/// Speed the wind is loudest at.
+	const MAX_SPEED: f32 = 20.0;
+	const FAR: f32 = 4.0;
+	const NEAR: f32 = 0.3;
+
+	/// Sounds follow the head, so `None` without one.
+	pub async fn new(client: &Arc<ClientHandle>) -> NodeResult<Option<Self>> {
+		let Some(hmd) = hmd(client).await else {
+			return Ok(None);
+		};
+		let sound = |name: &str| {
+			Sound::create(
+				&hmd,
+				Transform::from_translation([0.0, 0.0, -0.3]),
+				&ResourceID::new_namespaced(APP_ID, name),
+			)
+		};
+		Ok(Some(MotionAudio {
+			wind: (0..=Self::WIND_LAYER_SPEEDS.len())
+				.map(|layer| sound(&format!("wind_{layer}")))
+				.collect::<NodeResult<_>>()?,
+			playing: None,
+			mode_switch: sound("mode_switch")?,
+			brake: sound("brake")?,
+			limit: sound("limit")?,
+			at_limit: false,
+		}))
+	}
+
+	/// `speed` in m/s.
+	pub fn update(&mut self, speed: f32) {
+		if speed < Self::MIN_SPEED {
+			if let Some((layer, _)) = self.playing.take() {
+				_ = self.wind[layer].stop();
+			}
+			return;
+		}
+		let layer = Self::WIND_LAYER_SPEEDS
+			.iter()
+			.take_while(|layer_speed| speed >= **layer_speed)
+			.count();
+		let wind = &self.wind[layer];
+		let loudness = (speed / Self::MAX_SPEED).clamp(0.0, 1.0).sqrt();
+		_ = wind.set_local_transform(Transform::from_translation([
+			0.0,
+			0.0,
+			-(Self::FAR + (Self::NEAR - Self::FAR) * loudness),
+		]));
+		let looping = self.playing.is_some_and(|(playing, started)| {
+			playing == layer && started.elapsed() < Self::WIND_LOOP
+		});
+		if looping {
+			return;
+		}
+		if let Some((playing, _)) = self.playing
+			&& playing != layer
+		{
+			_ = self.wind[playing].stop();
+		}
+		_ = wind.play();
+		self.playing = Some((layer, Instant::now()));
+	}
+
+	pub fn mode_switched(&self) {
+		_ = self.mode_switch.play();
+	}
+	pub fn braked(&self) {
+		_ = self.brake.play();
+	}
+	/// Plays the limit cue once each time the user reaches the edge of the bounds.
+	pub fn update_limit(&mut self, edge: Option<Edge>) {
+		let at_limit = edge.is_some_and(|edge| edge.proximity >= 1.0);
+		if at_limit && !self.at_limit {
+			_ = self.limit.play();
+		}
+		self.at_limit = at_limit;
+	}
+}
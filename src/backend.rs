@@ -0,0 +1,62 @@
+use std::fmt;
+
+use glam::{Affine3A, Quat, Vec3};
+
+/// Something went wrong talking to the server or Monado.
+#[derive(Debug, Clone)]
+pub struct BackendError(pub String);
+impl BackendError {
+	pub fn new(err: impl fmt::Display) -> Self {
+		BackendError(err.to_string())
+	}
+}
+impl fmt::Display for BackendError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+impl std::error::Error for BackendError {}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OriginPose {
+	pub position: Vec3,
+	pub orientation: Quat,
+}
+impl Default for OriginPose {
+	fn default() -> Self {
+		OriginPose {
+			position: Vec3::ZERO,
+			orientation: Quat::IDENTITY,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingOrigin {
+	pub id: u32,
+	pub name: String,
+	pub offset: OriginPose,
+}
+
+/// Monado's tracking origins plus the stage they live in.
+pub trait TrackingOriginStore {
+	/// Transform of the velocity reference space relative to the stage.
+	async fn velocity_to_stage(&mut self) -> BackendResult<Affine3A>;
+	async fn origins(&mut self) -> BackendResult<Vec<TrackingOrigin>>;
+	/// Sets the offset of every origin in `offsets`, matched by id.
+	async fn set_offsets(&mut self, offsets: &[TrackingOrigin]) -> BackendResult<()>;
+}
+
+/// The world-root spatial that reparentable objects get attached to while moving.
+pub trait SpatialTree {
+	/// Transform of the velocity reference space relative to the world root.
+	async fn velocity_to_world(&mut self) -> BackendResult<Affine3A>;
+	/// Moves the world root to `translation` relative to the velocity reference space.
+	fn set_world_translation(&mut self, translation: Vec3) -> BackendResult<()>;
+	/// Attach every reparentable object to the world root as it appears, until `end_reparenting`.
+	fn begin_reparenting(&mut self);
+	/// Hand every reparentable object back to its previous parent.
+	fn end_reparenting(&mut self);
+}
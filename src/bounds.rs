@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use glam::{Affine3A, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	ClientHandle,
+	drawable::{Lines, LinesAspect as _},
+	node::NodeResult,
+	objects::hmd,
+	spatial::{SpatialAspect as _, SpatialRef, Transform},
+};
+use tracing::error;
+
+use crate::{pipelined_transform::PipelinedTransform, theme::Stroke};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundsShape {
+	Box { half_size: [f32; 3] },
+	Sphere { radius: f32 },
+}
+
+/// A volume in stage space (or the reparent world root) the user can't leave.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Bounds {
+	pub shape: BoundsShape,
+	#[serde(default)]
+	pub center: [f32; 3],
+	/// How far inside the edge the user starts slowing down.
+	#[serde(default = "Bounds::default_soft_edge")]
+	pub soft_edge: f32,
+	/// How quickly the user is pushed back when past the edge, per second.
+	#[serde(default = "Bounds::default_stiffness")]
+	pub stiffness: f32,
+}
+
+/// The closest point of the bounds' surface to the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+	/// Pointing out of the bounds.
+	pub normal: Vec3,
+	/// Negative inside the bounds.
+	pub distance: f32,
+	/// 0 before the soft edge, 1 at or past the edge.
+	pub proximity: f32,
+}
+
+impl Bounds {
+	fn default_soft_edge() -> f32 {
+		1.0
+	}
+	fn default_stiffness() -> f32 {
+		4.0
+	}
+
+	pub fn edge(&self, position: Vec3) -> Edge {
+		let local = position - Vec3::from(self.center);
+		let (normal, distance) = match self.shape {
+			BoundsShape::Sphere { radius } => (
+				local.try_normalize().unwrap_or(Vec3::Y),
+				local.length() - radius,
+			),
+			BoundsShape::Box { half_size } => {
+				let q = local.abs() - Vec3::from(half_size);
+				let outside = q.max(Vec3::ZERO);
+				if outside != Vec3::ZERO {
+					((outside * local.signum()).normalize(), outside.length())
+				} else {
+					let axis = if q.x >= q.y && q.x >= q.z {
+						Vec3::X
+					} else if q.y >= q.z {
+						Vec3::Y
+					} else {
+						Vec3::Z
+					};
+					(axis * local.signum(), q.max_element())
+				}
+			}
+		};
+		Edge {
+			normal,
+			distance,
+			proximity: ((distance + self.soft_edge) / self.soft_edge.max(f32::EPSILON))
+				.clamp(0.0, 1.0),
+		}
+	}
+
+	/// Slows `delta` down the closer `position` is to the edge and pushes back past it.
+	pub fn constrain(&self, position: Vec3, delta: Vec3, delta_secs: f32) -> (Vec3, Edge) {
+		let edge = self.edge(position);
+		let outward = delta.dot(edge.normal).max(0.0);
+		let mut delta = delta - edge.normal * outward * edge.proximity;
+		if edge.distance > 0.0 {
+			delta -= edge.normal * edge.distance * (self.stiffness * delta_secs).min(1.0);
+		}
+		(delta, edge)
+	}
+
+	/// Like `constrain` for movement in the space `mat` maps velocity into,
+	/// with the edge normal mapped back into the velocity reference space.
+	pub fn constrain_in(
+		bounds: Option<&Bounds>,
+		mat: Affine3A,
+		position: Vec3,
+		delta: Vec3,
+		delta_secs: f32,
+	) -> (Vec3, Option<Edge>) {
+		let Some(bounds) = bounds else {
+			return (delta, None);
+		};
+		let (delta, edge) = bounds.constrain(position, delta, delta_secs);
+		let normal = mat
+			.inverse()
+			.transform_vector3(edge.normal)
+			.normalize_or_zero();
+		(delta, Some(Edge { normal, ..edge }))
+	}
+}
+
+/// A patch of grid between the user and the edge of the bounds, fading in as they approach.
+pub struct BoundsSignifier {
+	lines: Lines,
+	/// Head relative to the root.
+	head: Option<PipelinedTransform>,
+	root: SpatialRef,
+	visible: bool,
+	stroke: Stroke,
+}
+
+impl BoundsSignifier {
+	const SIZE: f32 = 1.5;
+	const CELLS: usize = 6;
+
+	pub async fn new(client: &Arc<ClientHandle>, stroke: Stroke) -> NodeResult<Self> {
+		let root = client.get_root().clone().as_spatial_ref();
+		Ok(BoundsSignifier {
+			lines: Lines::create(client.get_root(), Transform::identity(), &[])?,
+			head: hmd(client)
+				.await
+				.map(|hmd| PipelinedTransform::new(hmd, root.clone())),
+			root,
+			visible: false,
+			stroke,
+		})
+	}
+
+	/// `edge` has its normal in the velocity reference space.
+	pub async fn update(&mut self, edge: Option<Edge>) {
+		let Some(edge) = edge.filter(|edge| edge.proximity > 0.0) else {
+			if self.visible {
+				self.visible = false;
+				_ = self.lines.set_lines(&[]);
+			}
+			return;
+		};
+		self.visible = true;
+		let Some(head) = &mut self.head else {
+			return;
+		};
+		let Ok(head) = head.get().await else {
+			return;
+		};
+		let head = Vec3::from(head.translation);
+		let position = head + edge.normal * (-edge.distance).max(0.3);
+		if let Err(err) = self.lines.set_relative_transform(
+			&self.root,
+			Transform::from_translation_rotation(
+				position,
+				Quat::from_rotation_arc(Vec3::Z, -edge.normal),
+			),
+		) {
+			error!("unable to move bounds signifier: {err}");
+		}
+
+		let stroke = self
+			.stroke
+			.with_alpha(self.stroke.color.0[3] * edge.proximity);
+		let half = Self::SIZE * 0.5;
+		let lines = (0..=Self::CELLS)
+			.map(|i| i as f32 / Self::CELLS as f32 * Self::SIZE - half)
+			.flat_map(|offset| {
+				[
+					[Vec3::new(offset, -half, 0.0), Vec3::new(offset, half, 0.0)],
+					[Vec3::new(-half, offset, 0.0), Vec3::new(half, offset, 0.0)],
+				]
+			})
+			.flat_map(|points| stroke.polyline(points, false, 0.002))
+			.collect::<Vec<_>>();
+		_ = self.lines.set_lines(&lines);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+
+	use super::{Bounds, BoundsShape};
+
+	#[test]
+	fn soft_edge_slows_and_pushes_back() {
+		let bounds = Bounds {
+			shape: BoundsShape::Box {
+				half_size: [5.0, 5.0, 5.0],
+			},
+			center: [0.0; 3],
+			soft_edge: 1.0,
+			stiffness: 4.0,
+		};
+		let delta = Vec3::X * 0.1;
+		assert_eq!(bounds.constrain(Vec3::ZERO, delta, 0.1).0, delta);
+		let (slowed, edge) = bounds.constrain(Vec3::X * 4.5, delta, 0.1);
+		assert_eq!(edge.normal, Vec3::X);
+		assert!(slowed.x > 0.0 && slowed.x < delta.x);
+		assert_eq!(bounds.constrain(Vec3::X * 4.5, -delta, 0.1).0, -delta);
+		let (pushed, _) = bounds.constrain(Vec3::X * 6.0, delta, 0.1);
+		assert!(pushed.x < 0.0);
+
+		let sphere = Bounds {
+			shape: BoundsShape::Sphere { radius: 2.0 },
+			..bounds
+		};
+		let edge = sphere.edge(Vec3::Z * 3.0);
+		assert_eq!(edge.normal, Vec3::Z);
+		assert_eq!(edge.distance, 1.0);
+		assert_eq!(edge.proximity, 1.0);
+	}
+}
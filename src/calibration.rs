@@ -0,0 +1,411 @@
+use std::{
+	fs, io,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	drawable::{Text, TextAspect as _},
+	input::{InputData, InputDataType},
+	node::NodeResult,
+	spatial::SpatialRefAspect,
+};
+use tracing::error;
+
+use crate::{readout::prompt, settings::Settings};
+
+/// A threshold that has to be crossed further to start than to stop, so jitter doesn't flicker.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Hysteresis {
+	pub press: f32,
+	pub release: f32,
+}
+impl Hysteresis {
+	/// Works for values that fall when pressed (press below release) as well as rise.
+	pub fn pressed(&self, value: f32, was_pressed: bool) -> bool {
+		let threshold = match was_pressed {
+			true => self.release,
+			false => self.press,
+		};
+		match self.press < self.release {
+			true => value < threshold,
+			false => value > threshold,
+		}
+	}
+
+	/// Between a value seen at rest and one seen while pressing.
+	fn between(rest: f32, active: f32) -> Self {
+		Hysteresis {
+			press: rest + (active - rest) * 0.6,
+			release: rest + (active - rest) * 0.3,
+		}
+	}
+}
+
+/// Raw values the gesture thresholds apply to, for one input on one frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GestureValues {
+	Hand {
+		pinch_distance: f32,
+		grab_strength: f32,
+	},
+	Controller {
+		select: f32,
+		grab: f32,
+	},
+}
+impl GestureValues {
+	pub fn of(data: &InputData) -> Option<Self> {
+		data.datamap.with_data(|datamap| match &data.input {
+			InputDataType::Hand(h) => Some(GestureValues::Hand {
+				pinch_distance: Vec3::from(h.thumb.tip.position)
+					.distance(h.index.tip.position.into()),
+				grab_strength: datamap.idx("grab_strength").as_f32(),
+			}),
+			InputDataType::Tip(_) => Some(GestureValues::Controller {
+				select: datamap.idx("select").as_f32(),
+				grab: datamap.idx("grab").as_f32(),
+			}),
+			_ => None,
+		})
+	}
+}
+
+/// Per-user gesture thresholds, saved under a profile name.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Thresholds {
+	/// Thumb to index tip distance, in meters.
+	pub pinch: Hysteresis,
+	pub hand_grab: Hysteresis,
+	pub controller_select: Hysteresis,
+	pub controller_grab: Hysteresis,
+	/// Thumb to index tip distance for the palm-up summon pinch, in meters.
+	pub summon_pinch: Hysteresis,
+	/// Grab strength of a hand held open for the stop palm brake.
+	pub open_hand: Hysteresis,
+}
+impl Default for Thresholds {
+	fn default() -> Self {
+		Thresholds {
+			pinch: Hysteresis {
+				press: 0.03,
+				release: 0.04,
+			},
+			hand_grab: Hysteresis {
+				press: 0.8,
+				release: 0.7,
+			},
+			controller_select: Hysteresis {
+				press: 0.01,
+				release: 0.005,
+			},
+			controller_grab: Hysteresis {
+				press: 0.9,
+				release: 0.8,
+			},
+			summon_pinch: Hysteresis {
+				press: 0.03,
+				release: 0.04,
+			},
+			open_hand: Hysteresis {
+				press: 0.2,
+				release: 0.3,
+			},
+		}
+	}
+}
+
+impl Thresholds {
+	fn path(profile: &str) -> Option<PathBuf> {
+		Some(
+			Settings::dir()?
+				.join("profiles")
+				.join(format!("{profile}.json")),
+		)
+	}
+
+	pub fn load(profile: &str) -> Self {
+		let Some(path) = Self::path(profile) else {
+			return Thresholds::default();
+		};
+		match fs::read_to_string(&path) {
+			Ok(thresholds) => serde_json::from_str(&thresholds)
+				.inspect_err(|err| error!("invalid thresholds in {}: {err}", path.display()))
+				.unwrap_or_default(),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Thresholds::default(),
+			Err(err) => {
+				error!("unable to read {}: {err}", path.display());
+				Thresholds::default()
+			}
+		}
+	}
+
+	pub fn save(&self, profile: &str) -> io::Result<()> {
+		let path = Self::path(profile)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		fs::write(path, serde_json::to_string_pretty(self)?)
+	}
+
+	/// Pinching a hand or pulling a controller's trigger.
+	pub fn pinching(&self, data: &InputData, was_pinching: bool) -> bool {
+		match GestureValues::of(data) {
+			Some(GestureValues::Hand { pinch_distance, .. }) => {
+				self.pinch.pressed(pinch_distance, was_pinching)
+			}
+			Some(GestureValues::Controller { select, .. }) => {
+				self.controller_select.pressed(select, was_pinching)
+			}
+			None => false,
+		}
+	}
+
+	/// Making a fist or squeezing a controller's grip.
+	pub fn grabbing(&self, data: &InputData, was_grabbing: bool) -> bool {
+		match GestureValues::of(data) {
+			Some(GestureValues::Hand { grab_strength, .. }) => {
+				self.hand_grab.pressed(grab_strength, was_grabbing)
+			}
+			Some(GestureValues::Controller { grab, .. }) => {
+				self.controller_grab.pressed(grab, was_grabbing)
+			}
+			None => false,
+		}
+	}
+
+	/// Pinching a hand for the summon pose.
+	pub fn summon_pinching(&self, data: &InputData, was_pinching: bool) -> bool {
+		match GestureValues::of(data) {
+			Some(GestureValues::Hand { pinch_distance, .. }) => {
+				self.summon_pinch.pressed(pinch_distance, was_pinching)
+			}
+			_ => false,
+		}
+	}
+
+	/// Holding a hand open, for the stop palm.
+	pub fn open_hand(&self, data: &InputData, was_open: bool) -> bool {
+		match GestureValues::of(data) {
+			Some(GestureValues::Hand { grab_strength, .. }) => {
+				self.open_hand.pressed(grab_strength, was_open)
+			}
+			_ => false,
+		}
+	}
+
+	/// Thresholds between recorded relaxed, pinching and grabbing poses.
+	/// Anything without enough samples, or where the poses overlap, keeps its default.
+	pub fn derive(
+		relaxed: &[GestureValues],
+		pinch: &[GestureValues],
+		grab: &[GestureValues],
+	) -> Self {
+		let hands = |samples: &[GestureValues], value: fn(f32, f32) -> f32| {
+			samples
+				.iter()
+				.filter_map(|sample| match *sample {
+					GestureValues::Hand {
+						pinch_distance,
+						grab_strength,
+					} => Some(value(pinch_distance, grab_strength)),
+					GestureValues::Controller { .. } => None,
+				})
+				.collect::<Vec<_>>()
+		};
+		let controllers = |samples: &[GestureValues], value: fn(f32, f32) -> f32| {
+			samples
+				.iter()
+				.filter_map(|sample| match *sample {
+					GestureValues::Controller { select, grab } => Some(value(select, grab)),
+					GestureValues::Hand { .. } => None,
+				})
+				.collect::<Vec<_>>()
+		};
+		let defaults = Thresholds::default();
+		let hand_pinch = Self::separate(
+			hands(relaxed, |pinch, _| pinch),
+			hands(pinch, |pinch, _| pinch),
+			false,
+		);
+		Thresholds {
+			pinch: hand_pinch.unwrap_or(defaults.pinch),
+			summon_pinch: hand_pinch.unwrap_or(defaults.summon_pinch),
+			// anything short of the relaxed hand closing up counts as open
+			open_hand: Self::separate(
+				hands(grab, |_, grab| grab),
+				hands(relaxed, |_, grab| grab),
+				false,
+			)
+			.unwrap_or(defaults.open_hand),
+			hand_grab: Self::separate(
+				hands(relaxed, |_, grab| grab),
+				hands(grab, |_, grab| grab),
+				true,
+			)
+			.unwrap_or(defaults.hand_grab),
+			controller_select: Self::separate(
+				controllers(relaxed, |select, _| select),
+				controllers(pinch, |select, _| select),
+				true,
+			)
+			.unwrap_or(defaults.controller_select),
+			controller_grab: Self::separate(
+				controllers(relaxed, |_, grab| grab),
+				controllers(grab, |_, grab| grab),
+				true,
+			)
+			.unwrap_or(defaults.controller_grab),
+		}
+	}
+
+	/// Splits values seen at rest from values seen while active, ignoring the outer
+	/// tenth of each as tracking jitter. `rising` if the value goes up when active.
+	fn separate(mut rest: Vec<f32>, mut active: Vec<f32>, rising: bool) -> Option<Hysteresis> {
+		const MIN_SAMPLES: usize = 10;
+		if rest.len() < MIN_SAMPLES || active.len() < MIN_SAMPLES {
+			return None;
+		}
+		rest.sort_by(f32::total_cmp);
+		active.sort_by(f32::total_cmp);
+		let percentile = |values: &[f32], p: f32| values[((values.len() - 1) as f32 * p) as usize];
+		let (rest, active) = match rising {
+			true => (percentile(&rest, 0.9), percentile(&active, 0.1)),
+			false => (percentile(&rest, 0.1), percentile(&active, 0.9)),
+		};
+		((active > rest) == rising && active != rest).then(|| Hysteresis::between(rest, active))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+	Relaxed,
+	Pinch,
+	Grab,
+}
+impl Step {
+	fn prompt(self) -> &'static str {
+		match self {
+			Step::Relaxed => "Relax your hands\nand let go of any triggers",
+			Step::Pinch => "Pinch with both hands\nor pull the triggers",
+			Step::Grab => "Make fists with both hands\nor squeeze the grips",
+		}
+	}
+}
+
+/// Walks the user through relaxed, pinching and grabbing poses to derive their thresholds.
+pub struct CalibrationWizard {
+	profile: String,
+	prompt: Text,
+	step: Step,
+	step_started: Instant,
+	relaxed: Vec<GestureValues>,
+	pinch: Vec<GestureValues>,
+	grab: Vec<GestureValues>,
+}
+
+impl CalibrationWizard {
+	/// Time to get into the pose before recording it.
+	const SETTLE: Duration = Duration::from_secs(2);
+	const RECORD: Duration = Duration::from_secs(3);
+
+	pub fn new(parent: &impl SpatialRefAspect, profile: String) -> NodeResult<Self> {
+		Ok(CalibrationWizard {
+			profile,
+			prompt: prompt(parent, Step::Relaxed.prompt())?,
+			step: Step::Relaxed,
+			step_started: Instant::now(),
+			relaxed: Vec::new(),
+			pinch: Vec::new(),
+			grab: Vec::new(),
+		})
+	}
+
+	/// Records every hand and controller seen this frame, returns the thresholds once
+	/// the last pose is recorded. They're already saved to the profile by then.
+	pub fn update<'a>(
+		&mut self,
+		inputs: impl IntoIterator<Item = &'a InputData>,
+	) -> Option<Thresholds> {
+		let elapsed = self.step_started.elapsed();
+		if elapsed > Self::SETTLE {
+			let samples = match self.step {
+				Step::Relaxed => &mut self.relaxed,
+				Step::Pinch => &mut self.pinch,
+				Step::Grab => &mut self.grab,
+			};
+			samples.extend(inputs.into_iter().filter_map(GestureValues::of));
+		}
+		if elapsed < Self::SETTLE + Self::RECORD {
+			return None;
+		}
+		self.step = match self.step {
+			Step::Relaxed => Step::Pinch,
+			Step::Pinch => Step::Grab,
+			Step::Grab => {
+				let thresholds = Thresholds::derive(&self.relaxed, &self.pinch, &self.grab);
+				if let Err(err) = thresholds.save(&self.profile) {
+					error!("unable to save thresholds for {}: {err}", self.profile);
+				}
+				return Some(thresholds);
+			}
+		};
+		self.step_started = Instant::now();
+		_ = self.prompt.set_text(self.step.prompt());
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{GestureValues, Hysteresis, Thresholds};
+
+	#[test]
+	fn hysteresis_in_both_directions() {
+		let rising = Hysteresis::between(0.2, 1.0);
+		assert!(!rising.pressed(0.6, false));
+		assert!(rising.pressed(0.75, false));
+		assert!(rising.pressed(0.6, true));
+		assert!(!rising.pressed(0.4, true));
+
+		let falling = Hysteresis::between(0.08, 0.01);
+		assert!(falling.pressed(0.03, false));
+		assert!(!falling.pressed(0.05, false));
+		assert!(falling.pressed(0.05, true));
+	}
+
+	#[test]
+	fn derives_thresholds_between_poses() {
+		let hand = |pinch_distance, grab_strength| GestureValues::Hand {
+			pinch_distance,
+			grab_strength,
+		};
+		let relaxed = (0..30)
+			.map(|i| hand(0.06 + i as f32 * 0.001, 0.1))
+			.collect::<Vec<_>>();
+		let pinch = (0..30)
+			.map(|i| hand(0.01 + i as f32 * 0.0002, 0.3))
+			.collect::<Vec<_>>();
+		let grab = (0..30).map(|_| hand(0.05, 0.6)).collect::<Vec<_>>();
+		let thresholds = Thresholds::derive(&relaxed, &pinch, &grab);
+
+		// small hands: pinches close to 0.016 and relax from 0.062 out
+		assert!(thresholds.pinch.press > 0.016 && thresholds.pinch.press < 0.062);
+		assert!(thresholds.pinch.release > thresholds.pinch.press);
+		// weak grab tracking still separates from relaxed
+		assert!(thresholds.hand_grab.pressed(0.6, false));
+		assert!(!thresholds.hand_grab.pressed(0.1, false));
+		assert!(thresholds.open_hand.pressed(0.1, false));
+		assert!(!thresholds.open_hand.pressed(0.6, false));
+		assert_eq!(thresholds.summon_pinch, thresholds.pinch);
+		// no controllers were recorded
+		assert_eq!(
+			thresholds.controller_grab,
+			Thresholds::default().controller_grab
+		);
+	}
+}
@@ -0,0 +1,199 @@
+use std::{f32::consts::FRAC_PI_2, sync::Arc};
+
+use glam::{Affine3A, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	ClientHandle,
+	input::Hand,
+	node::NodeResult,
+	objects::hmd,
+	spatial::{Spatial, SpatialAspect as _, SpatialRef, SpatialRefAspect, Transform},
+};
+use tracing::error;
+
+use crate::pipelined_transform::PipelinedTransform;
+
+/// Where on the body a released pen docks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DockAnchor {
+	Hip,
+	LeftWrist,
+	RightWrist,
+	/// Just below the head.
+	Chest,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DockSettings {
+	pub anchor: DockAnchor,
+	/// How close to the anchor the pen has to be released to dock.
+	#[serde(default = "DockSettings::default_reach")]
+	pub reach: f32,
+}
+impl Default for DockSettings {
+	fn default() -> Self {
+		DockSettings {
+			anchor: DockAnchor::Hip,
+			reach: Self::default_reach(),
+		}
+	}
+}
+impl DockSettings {
+	fn default_reach() -> f32 {
+		0.25
+	}
+}
+
+impl DockAnchor {
+	/// Pose of the anchor given the head's pose, both in the same space.
+	/// Hip and chest only follow the head's yaw so looking around doesn't swing the pen.
+	pub fn pose_from_head(self, head: Affine3A) -> Option<Affine3A> {
+		let (offset, tilt) = match self {
+			DockAnchor::Hip => (Vec3::new(0.2, -0.6, 0.0), 0.0),
+			DockAnchor::Chest => (Vec3::new(0.0, -0.3, -0.15), -FRAC_PI_2 * 0.5),
+			DockAnchor::LeftWrist | DockAnchor::RightWrist => return None,
+		};
+		let forward = head.transform_vector3(Vec3::NEG_Z);
+		let yaw = Quat::from_rotation_y(f32::atan2(-forward.x, -forward.z));
+		let position = Vec3::from(head.translation) + yaw * offset;
+		Some(Affine3A::from_rotation_translation(
+			yaw * Quat::from_rotation_x(tilt),
+			position,
+		))
+	}
+
+	/// Pose of the anchor on `hand`, lying along the back of the forearm.
+	pub fn pose_on_hand(self, hand: &Hand) -> Option<Affine3A> {
+		let right = match self {
+			DockAnchor::LeftWrist => false,
+			DockAnchor::RightWrist => true,
+			DockAnchor::Hip | DockAnchor::Chest => return None,
+		};
+		if hand.right != right {
+			return None;
+		}
+		Some(Affine3A::from_rotation_translation(
+			Quat::from(hand.wrist.rotation) * Quat::from_rotation_x(FRAC_PI_2),
+			hand.wrist.position.into(),
+		))
+	}
+}
+
+/// A spatial kept on the user's body that the pen can be parented to.
+pub struct PenDock {
+	settings: DockSettings,
+	anchor: Spatial,
+	/// Head relative to the input handler.
+	head: Option<PipelinedTransform>,
+	/// Last known anchor pose relative to the input handler.
+	pose: Option<Affine3A>,
+	docked: bool,
+}
+
+impl PenDock {
+	pub async fn new(
+		client: &Arc<ClientHandle>,
+		settings: DockSettings,
+		handler: SpatialRef,
+	) -> NodeResult<Self> {
+		Ok(PenDock {
+			settings,
+			anchor: Spatial::create(client.get_root(), Transform::identity())?,
+			head: hmd(client)
+				.await
+				.map(|hmd| PipelinedTransform::new(hmd, handler)),
+			pose: None,
+			docked: false,
+		})
+	}
+
+	pub fn anchor(&self) -> DockAnchor {
+		self.settings.anchor
+	}
+	pub fn is_docked(&self) -> bool {
+		self.docked
+	}
+
+	/// Moves the anchor to `pose`, relative to `handler`.
+	pub fn set_pose(&mut self, handler: &impl SpatialRefAspect, pose: Affine3A) {
+		let (_, rotation, translation) = pose.to_scale_rotation_translation();
+		if let Err(err) = self.anchor.set_relative_transform(
+			handler,
+			Transform::from_translation_rotation(translation, rotation),
+		) {
+			error!("unable to move pen dock: {err}");
+		}
+		self.pose = Some(pose);
+	}
+
+	/// Follows the head for anchors that hang off of it, wrists are set from hand input.
+	pub async fn update(&mut self, handler: &impl SpatialRefAspect) {
+		let Some(head) = &mut self.head else {
+			return;
+		};
+		if matches!(
+			self.settings.anchor,
+			DockAnchor::LeftWrist | DockAnchor::RightWrist
+		) {
+			return;
+		}
+		let Ok(head) = head.get().await else {
+			return;
+		};
+		if let Some(pose) = self.settings.anchor.pose_from_head(head) {
+			self.set_pose(handler, pose);
+		}
+	}
+
+	/// Docks `pen` if it was released (at `position`, relative to the handler) within reach.
+	pub fn try_dock(&mut self, pen: &Spatial, position: Vec3) -> bool {
+		let Some(pose) = self.pose else {
+			return false;
+		};
+		if Vec3::from(pose.translation).distance(position) > self.settings.reach {
+			return false;
+		}
+		let docked = pen
+			.set_spatial_parent(&self.anchor)
+			.and_then(|_| pen.set_local_transform(Transform::identity()));
+		if let Err(err) = docked {
+			error!("unable to dock pen: {err}");
+			return false;
+		}
+		self.docked = true;
+		true
+	}
+
+	pub fn undock(&mut self, pen: &Spatial, root: &impl SpatialRefAspect) {
+		if !self.docked {
+			return;
+		}
+		if let Err(err) = pen.set_spatial_parent_in_place(root) {
+			error!("unable to undock pen: {err}");
+		}
+		self.docked = false;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Affine3A, Quat, Vec3};
+
+	use super::DockAnchor;
+
+	#[test]
+	fn hip_follows_head_yaw_only() {
+		let head = Affine3A::from_rotation_translation(
+			Quat::from_rotation_y(std::f32::consts::FRAC_PI_2) * Quat::from_rotation_x(-0.8),
+			Vec3::new(1.0, 1.7, 0.0),
+		);
+		let hip = DockAnchor::Hip.pose_from_head(head).unwrap();
+		let position = Vec3::from(hip.translation);
+		assert!((position.y - 1.1).abs() < 1e-5);
+		// facing -X, so the right hip is towards -Z
+		assert!(position.z < -0.19 && (position.x - 1.0).abs() < 1e-5);
+		assert!(hip.transform_vector3(Vec3::Y).abs_diff_eq(Vec3::Y, 1e-5));
+		assert_eq!(DockAnchor::LeftWrist.pose_from_head(head), None);
+	}
+}
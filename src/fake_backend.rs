@@ -0,0 +1,164 @@
+use glam::{Affine3A, Vec3};
+
+use crate::backend::{
+	BackendError, BackendResult, OriginPose, SpatialTree, TrackingOrigin, TrackingOriginStore,
+};
+
+/// In-memory tracking origins, for running locomotion without Monado.
+pub struct FakeOrigins {
+	pub origins: Vec<TrackingOrigin>,
+	pub velocity_to_stage: Affine3A,
+}
+
+impl FakeOrigins {
+	pub fn new(names: &[&str]) -> Self {
+		FakeOrigins {
+			origins: names
+				.iter()
+				.zip(0..)
+				.map(|(name, id)| TrackingOrigin {
+					id,
+					name: name.to_string(),
+					offset: OriginPose::default(),
+				})
+				.collect(),
+			velocity_to_stage: Affine3A::IDENTITY,
+		}
+	}
+
+	#[cfg(test)]
+	pub fn offset(&self, name: &str) -> Option<OriginPose> {
+		self.origins
+			.iter()
+			.find(|origin| origin.name == name)
+			.map(|origin| origin.offset)
+	}
+}
+
+impl TrackingOriginStore for FakeOrigins {
+	async fn velocity_to_stage(&mut self) -> BackendResult<Affine3A> {
+		Ok(self.velocity_to_stage)
+	}
+
+	async fn origins(&mut self) -> BackendResult<Vec<TrackingOrigin>> {
+		Ok(self.origins.clone())
+	}
+
+	async fn set_offsets(&mut self, offsets: &[TrackingOrigin]) -> BackendResult<()> {
+		for new in offsets {
+			let origin = self
+				.origins
+				.iter_mut()
+				.find(|origin| origin.id == new.id)
+				.ok_or_else(|| BackendError(format!("no tracking origin with id {}", new.id)))?;
+			origin.offset = new.offset;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeNode(usize);
+
+/// An in-memory spatial hierarchy standing in for the server.
+pub struct FakeSpatialTree {
+	/// Parent and transform relative to the parent, indexed by `FakeNode`.
+	nodes: Vec<(Option<FakeNode>, Affine3A)>,
+	velocity_ref: FakeNode,
+	world: FakeNode,
+	reparenting: bool,
+	pub reparent_sessions: usize,
+}
+
+impl Default for FakeSpatialTree {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl FakeSpatialTree {
+	pub const ROOT: FakeNode = FakeNode(0);
+
+	pub fn new() -> Self {
+		let mut tree = FakeSpatialTree {
+			nodes: vec![(None, Affine3A::IDENTITY)],
+			velocity_ref: Self::ROOT,
+			world: Self::ROOT,
+			reparenting: false,
+			reparent_sessions: 0,
+		};
+		tree.velocity_ref = tree.add_node(Self::ROOT, Affine3A::IDENTITY);
+		tree.world = tree.add_node(Self::ROOT, Affine3A::IDENTITY);
+		tree
+	}
+
+	pub fn add_node(&mut self, parent: FakeNode, transform: Affine3A) -> FakeNode {
+		self.nodes.push((Some(parent), transform));
+		FakeNode(self.nodes.len() - 1)
+	}
+
+	#[cfg(test)]
+	pub fn velocity_ref(&self) -> FakeNode {
+		self.velocity_ref
+	}
+	#[cfg(test)]
+	pub fn world(&self) -> FakeNode {
+		self.world
+	}
+	#[cfg(test)]
+	pub fn is_reparenting(&self) -> bool {
+		self.reparenting
+	}
+
+	pub fn global_transform(&self, node: FakeNode) -> Affine3A {
+		let (parent, local) = self.nodes[node.0];
+		match parent {
+			Some(parent) => self.global_transform(parent) * local,
+			None => local,
+		}
+	}
+
+	/// Transform of `node` in the space of `relative_to`, like `get_transform`.
+	pub fn relative_transform(&self, node: FakeNode, relative_to: FakeNode) -> Affine3A {
+		self.global_transform(relative_to).inverse() * self.global_transform(node)
+	}
+
+	/// Like `set_relative_transform` with every field set.
+	pub fn set_relative_transform(
+		&mut self,
+		node: FakeNode,
+		relative_to: FakeNode,
+		transform: Affine3A,
+	) {
+		let parent_global = match self.nodes[node.0].0 {
+			Some(parent) => self.global_transform(parent),
+			None => Affine3A::IDENTITY,
+		};
+		self.nodes[node.0].1 =
+			parent_global.inverse() * self.global_transform(relative_to) * transform;
+	}
+}
+
+impl SpatialTree for FakeSpatialTree {
+	async fn velocity_to_world(&mut self) -> BackendResult<Affine3A> {
+		Ok(self.relative_transform(self.velocity_ref, self.world))
+	}
+
+	fn set_world_translation(&mut self, translation: Vec3) -> BackendResult<()> {
+		let mut transform = self.relative_transform(self.world, self.velocity_ref);
+		transform.translation = translation.into();
+		self.set_relative_transform(self.world, self.velocity_ref, transform);
+		Ok(())
+	}
+
+	fn begin_reparenting(&mut self) {
+		if !self.reparenting {
+			self.reparenting = true;
+			self.reparent_sessions += 1;
+		}
+	}
+
+	fn end_reparenting(&mut self) {
+		self.reparenting = false;
+	}
+}
@@ -0,0 +1,242 @@
+use std::time::{Duration, Instant};
+
+use glam::{Mat4, Vec3};
+use stardust_xr_fusion::{
+	drawable::{Lines, LinesAspect as _, Text, TextAspect as _},
+	node::NodeResult,
+	spatial::{SpatialRef, SpatialRefAspect, Transform},
+};
+use stardust_xr_molecules::lines::{LineExt as _, circle};
+use tracing::error;
+
+use crate::{
+	pipelined_transform::PipelinedTransform,
+	readout::prompt,
+	theme::{Stroke, Theme},
+};
+
+/// The tip held still on the floor so far.
+#[derive(Debug, Clone, Copy)]
+struct Hold {
+	low: f32,
+	high: f32,
+	sum: Vec3,
+	samples: u32,
+	secs: f32,
+	/// Already measured, so holding on doesn't keep measuring.
+	measured: bool,
+}
+impl Hold {
+	fn new(tip: Vec3) -> Self {
+		Hold {
+			low: tip.y,
+			high: tip.y,
+			sum: Vec3::ZERO,
+			samples: 0,
+			secs: 0.0,
+			measured: false,
+		}
+	}
+}
+
+/// Where the pen tip rests on the physical floor, in stage space.
+#[derive(Debug, Default)]
+pub struct FloorMeasure {
+	hold: Option<Hold>,
+	measured: Option<Vec3>,
+}
+impl FloorMeasure {
+	/// Most the tip can wander up and down while still counting as held still.
+	const STILL: f32 = 0.005;
+	const HOLD_SECS: f32 = 1.0;
+	/// Tracking further off than this is more likely the tip resting on a table.
+	const MAX_ERROR: f32 = 0.15;
+
+	/// Returns true if this sample finished a measurement.
+	pub fn sample(&mut self, delta_secs: f32, tip: Vec3, floor: f32) -> bool {
+		let hold = match &mut self.hold {
+			Some(hold) if tip.y.max(hold.high) - tip.y.min(hold.low) <= Self::STILL => hold,
+			hold => hold.insert(Hold::new(tip)),
+		};
+		hold.low = hold.low.min(tip.y);
+		hold.high = hold.high.max(tip.y);
+		hold.sum += tip;
+		hold.samples += 1;
+		hold.secs += delta_secs;
+		if hold.measured || hold.secs < Self::HOLD_SECS {
+			return false;
+		}
+		let mean = hold.sum / hold.samples as f32;
+		if (mean.y - floor).abs() > Self::MAX_ERROR {
+			return false;
+		}
+		hold.measured = true;
+		self.measured = Some(mean);
+		true
+	}
+
+	pub fn measured(&self) -> Option<Vec3> {
+		self.measured
+	}
+
+	/// How far to raise the origins so the measured spot ends up on `floor`.
+	pub fn correction(&self, floor: f32) -> Option<f32> {
+		self.measured.map(|measured| floor - measured.y)
+	}
+}
+
+/// Walks the user through touching the floor, previews the error and hands back the
+/// correction once they double pinch.
+pub struct FloorLeveling {
+	measure: FloorMeasure,
+	root_to_stage: PipelinedTransform,
+	/// Stage height the floor should be at.
+	floor: f32,
+	prompt: Text,
+	preview: Lines,
+	/// Where the tip touched, and where the floor should be.
+	touched: Stroke,
+	expected: Stroke,
+	applied: Option<Instant>,
+}
+
+impl FloorLeveling {
+	/// How long the result stays up after applying.
+	const LINGER: Duration = Duration::from_secs(3);
+
+	/// `prompt_parent` is best the head, `stage` the play space.
+	pub fn new(
+		prompt_parent: &impl SpatialRefAspect,
+		root: SpatialRef,
+		stage: SpatialRef,
+		floor: f32,
+		theme: &Theme,
+	) -> NodeResult<Self> {
+		Ok(FloorLeveling {
+			measure: FloorMeasure::default(),
+			preview: Lines::create(&stage, Transform::identity(), &[])?,
+			root_to_stage: PipelinedTransform::new(root, stage),
+			floor,
+			prompt: prompt(prompt_parent, "Hold the pen tip still on the floor")?,
+			touched: theme.guide,
+			expected: theme.target,
+			applied: None,
+		})
+	}
+
+	/// Still waiting on a double pinch to confirm, rather than showing the result.
+	pub fn awaiting_input(&self) -> bool {
+		self.applied.is_none()
+	}
+
+	/// Done showing the result, so it can be dropped.
+	pub fn finished(&self) -> bool {
+		self.applied
+			.is_some_and(|applied| applied.elapsed() > Self::LINGER)
+	}
+
+	/// `tip` is in the velocity reference space. Returns how far to raise the origins in
+	/// stage space once the user confirms with a double pinch.
+	pub async fn update(
+		&mut self,
+		delta_secs: f32,
+		tip: Option<Vec3>,
+		confirm: bool,
+	) -> Option<f32> {
+		if self.applied.is_some() {
+			return None;
+		}
+		if let Some(tip) = tip {
+			match self.root_to_stage.get().await {
+				Ok(root_to_stage) => {
+					let tip = root_to_stage.transform_point3(tip);
+					if self.measure.sample(delta_secs, tip, self.floor) {
+						self.show_measurement();
+					}
+				}
+				Err(err) => error!("unable to get velocity_ref to stage transform: {err}"),
+			}
+		}
+		if !confirm {
+			return None;
+		}
+		let correction = self.measure.correction(self.floor)?;
+		self.applied = Some(Instant::now());
+		_ = self.preview.set_lines(&[]);
+		_ = self.prompt.set_text(&format!(
+			"Moved the floor {:.1} cm\nUndo to put it back",
+			correction.abs() * 100.0
+		));
+		Some(correction)
+	}
+
+	fn show_measurement(&self) {
+		let (Some(measured), Some(correction)) =
+			(self.measure.measured(), self.measure.correction(self.floor))
+		else {
+			return;
+		};
+		let direction = match correction > 0.0 {
+			true => "below",
+			false => "above",
+		};
+		_ = self.prompt.set_text(&format!(
+			"The floor is {:.1} cm {direction} where it should be\nDouble pinch to fix it, or touch again",
+			correction.abs() * 100.0
+		));
+
+		// a ring where the tip touched and one where the floor should be, joined up
+		let expected = measured.with_y(self.floor);
+		let ring = |center: Vec3, stroke: Stroke| {
+			stroke.draw(
+				circle(32, 0.0, 0.05).transform(
+					Mat4::from_translation(center)
+						* Mat4::from_rotation_x(std::f32::consts::FRAC_PI_2),
+				),
+				0.002,
+			)
+		};
+		let lines = [
+			ring(measured, self.touched),
+			ring(expected, self.expected),
+			self.touched.polyline([measured, expected], false, 0.002),
+		]
+		.concat();
+		if let Err(err) = self.preview.set_lines(&lines) {
+			error!("unable to show floor preview: {err}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+
+	use super::FloorMeasure;
+
+	const FRAME: f32 = 1.0 / 90.0;
+
+	#[test]
+	fn measures_a_still_touch_near_the_floor() {
+		let mut measure = FloorMeasure::default();
+		let mut measured = 0;
+		for frame in 0..120 {
+			// tracking jitter of a couple of millimetres
+			let jitter = (frame % 3) as f32 * 0.001;
+			measured += measure.sample(FRAME, Vec3::new(1.0, 0.03 + jitter, 2.0), 0.0) as usize;
+		}
+		assert_eq!(measured, 1);
+		let correction = measure.correction(0.0).unwrap();
+		assert!((correction + 0.031).abs() < 1e-3, "{correction}");
+
+		// moving keeps it from measuring, and resting on a table is too far off
+		let mut measure = FloorMeasure::default();
+		for frame in 0..120 {
+			measure.sample(FRAME, Vec3::Y * (0.03 + frame as f32 * 0.001), 0.0);
+		}
+		for _ in 0..120 {
+			measure.sample(FRAME, Vec3::Y * 0.75, 0.0);
+		}
+		assert_eq!(measure.measured(), None);
+	}
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use glam::Vec3;
+
+use crate::backend::{OriginPose, TrackingOrigin};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryAction {
+	Undo,
+	Redo,
+}
+
+/// Where a movement backend had put the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+	/// Every Monado origin's offset.
+	Origins(Vec<TrackingOrigin>),
+	/// Translation of the reparent world root relative to the velocity reference space.
+	World(Vec3),
+	/// Translation of the zone root relative to the velocity reference space.
+	Zone(Vec3),
+}
+impl Placement {
+	/// Between `self` at 0 and `to` at 1, or `None` if they're from different backends.
+	pub fn lerp(&self, to: &Placement, t: f32) -> Option<Placement> {
+		match (self, to) {
+			(Placement::World(from), Placement::World(to)) => {
+				Some(Placement::World(from.lerp(*to, t)))
+			}
+			(Placement::Zone(from), Placement::Zone(to)) => {
+				Some(Placement::Zone(from.lerp(*to, t)))
+			}
+			(Placement::Origins(from), Placement::Origins(to)) => Some(Placement::Origins(
+				from.iter()
+					.filter_map(|from| {
+						let to = to.iter().find(|to| to.id == from.id)?;
+						Some(TrackingOrigin {
+							offset: OriginPose {
+								position: from.offset.position.lerp(to.offset.position, t),
+								orientation: from
+									.offset
+									.orientation
+									.slerp(to.offset.orientation, t),
+							},
+							..from.clone()
+						})
+					})
+					.collect(),
+			)),
+			_ => None,
+		}
+	}
+}
+
+/// One continuous motion, from where it started to where it came to rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+	pub start: Placement,
+	pub end: Placement,
+}
+
+/// Recent movement segments, to undo and redo.
+#[derive(Debug, Default)]
+pub struct History {
+	done: VecDeque<Segment>,
+	undone: Vec<Segment>,
+}
+impl History {
+	const CAPACITY: usize = 32;
+
+	pub fn push(&mut self, segment: Segment) {
+		self.undone.clear();
+		self.done.push_back(segment);
+		if self.done.len() > Self::CAPACITY {
+			self.done.pop_front();
+		}
+	}
+
+	/// The flight back along the last segment.
+	pub fn undo(&mut self) -> Option<Flight> {
+		let segment = self.done.pop_back()?;
+		let flight = Flight::new(segment.end.clone(), segment.start.clone());
+		self.undone.push(segment);
+		Some(flight)
+	}
+
+	/// The flight along the last undone segment again.
+	pub fn redo(&mut self) -> Option<Flight> {
+		let segment = self.undone.pop()?;
+		let flight = Flight::new(segment.start.clone(), segment.end.clone());
+		self.done.push_back(segment);
+		Some(flight)
+	}
+}
+
+/// Flying from one placement to another.
+#[derive(Debug, Clone)]
+pub struct Flight {
+	from: Placement,
+	to: Placement,
+	elapsed: f32,
+}
+impl Flight {
+	const DURATION: f32 = 0.6;
+
+	pub fn new(from: Placement, to: Placement) -> Self {
+		Flight {
+			from,
+			to,
+			elapsed: 0.0,
+		}
+	}
+
+	/// Where to be after another `delta_secs`, and whether that's the end.
+	pub fn step(&mut self, delta_secs: f32) -> (Option<Placement>, bool) {
+		self.elapsed += delta_secs;
+		let t = (self.elapsed / Self::DURATION).min(1.0);
+		// smoothstep, easing in and out of the flight
+		let eased = t * t * (3.0 - 2.0 * t);
+		(self.from.lerp(&self.to, eased), t >= 1.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+
+	use super::{History, Placement, Segment};
+
+	fn segment(from: f32, to: f32) -> Segment {
+		Segment {
+			start: Placement::World(Vec3::X * from),
+			end: Placement::World(Vec3::X * to),
+		}
+	}
+
+	#[test]
+	fn undo_flies_back_and_redo_forward() {
+		let mut history = History::default();
+		history.push(segment(0.0, 1.0));
+		history.push(segment(1.0, 3.0));
+
+		let mut undo = history.undo().unwrap();
+		assert_eq!(undo.step(0.3).0, Some(Placement::World(Vec3::X * 2.0)));
+		assert_eq!(undo.step(1.0), (Some(Placement::World(Vec3::X)), true));
+
+		let mut redo = history.redo().unwrap();
+		assert_eq!(
+			redo.step(1.0),
+			(Some(Placement::World(Vec3::X * 3.0)), true)
+		);
+		assert!(history.redo().is_none());
+
+		// a new segment drops what was undone
+		history.undo();
+		history.push(segment(1.0, -1.0));
+		assert!(history.redo().is_none());
+	}
+
+	#[test]
+	fn history_is_bounded() {
+		let mut history = History::default();
+		for i in 0..100 {
+			history.push(segment(i as f32, i as f32 + 1.0));
+		}
+		assert_eq!(
+			std::iter::from_fn(|| history.undo()).count(),
+			History::CAPACITY
+		);
+	}
+}
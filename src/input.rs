@@ -1,18 +1,19 @@
-use std::{f32::consts::FRAC_PI_2, process, sync::Arc};
+use std::{
+	f32::consts::FRAC_PI_2,
+	sync::Arc,
+	time::{Duration, Instant},
+};
 
-use glam::{Mat4, Quat, Vec3, vec3};
+use glam::{Affine3A, Mat4, Quat, Vec3, vec3};
 use stardust_xr_fusion::{
 	ClientHandle,
-	drawable::{Line, LinePoint, Lines, LinesAspect as _, Model},
+	drawable::{Line, Lines, LinesAspect as _, Model},
 	fields::{CylinderShape, Field, Shape},
-	input::{InputData, InputDataType, InputHandler},
+	input::{Hand, InputData, InputDataType, InputHandler},
 	node::NodeResult,
 	objects::hmd,
 	spatial::{Spatial, SpatialAspect as _, SpatialRef, SpatialRefAspect, Transform},
-	values::{
-		ResourceID,
-		color::{rgba, rgba_linear},
-	},
+	values::ResourceID,
 	zbus::Connection,
 };
 use stardust_xr_molecules::{
@@ -22,27 +23,77 @@ use stardust_xr_molecules::{
 	lines::{LineExt as _, circle},
 	reparentable::Reparentable,
 };
+use tokio::sync::mpsc;
 use tracing::error;
 
 use crate::{
 	APP_ID,
+	bounds::Edge,
+	calibration::{CalibrationWizard, Thresholds},
+	dock::PenDock,
+	history::HistoryAction,
+	locomotion::{GearShift, Gears, Mode, Pointing, WaftSample},
 	mode_button::ModeButton,
-	solar_sailer::{Mode, mat_from_transform},
+	pen_model::{PenModel, PenState},
+	pipelined_transform::PipelinedTransform,
+	readout::Readout,
+	settings::{Accessibility, Settings, Steering},
+	solar_sailer::mat_from_transform,
+	summon::{Summon, SummonTarget, listen_for_summons},
+	theme::Theme,
 };
 
 pub struct PenInput {
 	move_action: SimpleAction,
 	grab_action: SingleAction,
+	accessibility: Accessibility,
+	steering: Steering,
+	/// Only followed when steering by gaze.
+	head_to_root: Option<PipelinedTransform>,
+	/// Thrust toggled on by a pinch, with `latch_thrust`.
+	thrust_latched: bool,
+	precision_action: SimpleAction,
+	brake_action: SimpleAction,
+	/// When the brake pose started being held, and whether it already braked.
+	brake_held: Option<(Instant, bool)>,
+	/// When the last pinch on the held pen started, to catch double pinches.
+	last_pinch: Option<Instant>,
+	pick_requested: bool,
+	/// Every hand and controller, to follow a wrist dock and calibrate.
+	tracked_action: SimpleAction,
+	handler_to_root: Option<Affine3A>,
+	thresholds: Thresholds,
+	theme: Theme,
+	calibration: Option<CalibrationWizard>,
+	dock: Option<PenDock>,
+	/// Where the pen was last held, relative to the input handler.
+	grab_position: Option<Vec3>,
+	summon_action: SimpleAction,
+	summon_held: Option<(Instant, bool)>,
+	summon_requests: mpsc::UnboundedReceiver<()>,
+	summon: Option<Summon>,
+	hmd: Option<SpatialRef>,
+	/// Where a pen summoned to the head flies to, relative to the input handler.
+	head_to_handler: Option<PipelinedTransform>,
 	field: Field,
 	pen_root: Spatial,
 	queue: InputQueue,
-	prev_position: Option<Vec3>,
 	signifiers: Lines,
 	client: Arc<ClientHandle>,
 	button: Button,
+	gear_button: Button,
+	undo_button: Button,
+	redo_button: Button,
+	/// Controller thumbstick past the gear shift threshold, so each push shifts once.
+	gear_scrolled: bool,
+	gear_shift: Option<GearShift>,
 	reparentable: Option<Reparentable>,
 	derezzable: Derezzable,
+	/// The pen was derezzed, which quits.
+	derezzed: bool,
 	connection: Connection,
+	model: PenModel,
+	readout: Readout,
 	_button_model: Model,
 }
 #[allow(dead_code, clippy::large_enum_variant)]
@@ -54,18 +105,27 @@ pub struct GrabInput {
 	move_action: SingleAction,
 	_field: Field,
 	queue: InputQueue,
-	prev_position: Option<Vec3>,
 	signifiers: Lines,
-	client: Arc<ClientHandle>,
 	button_hand: Option<ModeButton>,
 	button_controller: Option<ModeButton>,
+	thresholds: Thresholds,
+	theme: Theme,
+	/// The handler follows the head, so this keeps changing. Never more than a couple of
+	/// frames behind, which keeps head motion from turning into much thrust.
+	handler_to_root: PipelinedTransform,
 }
 
 impl Input {
-	pub async fn new_pen(client: &Arc<ClientHandle>, connection: Connection) -> NodeResult<Self> {
-		PenInput::new(client, connection).await.map(Input::Pen)
+	pub async fn new_pen(
+		client: &Arc<ClientHandle>,
+		connection: Connection,
+		settings: &Settings,
+	) -> NodeResult<Self> {
+		PenInput::new(client, connection, settings)
+			.await
+			.map(Input::Pen)
 	}
-	pub async fn new_grab(client: &Arc<ClientHandle>) -> NodeResult<Self> {
+	pub async fn new_grab(client: &Arc<ClientHandle>, settings: &Settings) -> NodeResult<Self> {
 		let field = Field::create(
 			&hmd(client).await.unwrap(),
 			Transform::identity(),
@@ -76,15 +136,20 @@ impl Input {
 		)
 		.unwrap();
 		let queue = InputHandler::create(&field, Transform::identity(), &field)?.queue()?;
+		let handler_to_root = PipelinedTransform::new(
+			queue.handler().clone().as_spatial().as_spatial_ref(),
+			client.get_root().clone().as_spatial_ref(),
+		);
 		Ok(Input::Grab(GrabInput {
 			signifiers: Lines::create(queue.handler(), Transform::identity(), &[]).unwrap(),
 			move_action: SingleAction::default(),
 			_field: field,
 			queue,
-			prev_position: None,
-			client: client.clone(),
 			button_hand: None,
 			button_controller: None,
+			thresholds: Thresholds::load(&settings.profile),
+			theme: Theme::load(&settings.theme),
+			handler_to_root,
 		}))
 	}
 }
@@ -101,35 +166,157 @@ impl Input {
 			Input::Pen(pen_input) => pen_input.handle_input(),
 		}
 	}
-	pub async fn waft(&mut self, delta_secs: f32) -> Vec3 {
+	/// Asked to quit, by derezzing the pen.
+	pub fn derezzed(&self) -> bool {
 		match self {
-			Input::Grab(grab_input) => grab_input.waft(delta_secs).await,
-			Input::Pen(pen_input) => pen_input.waft(delta_secs).await,
+			Input::Grab(_) => false,
+			Input::Pen(pen_input) => pen_input.derezzed,
 		}
 	}
-	pub fn update_signifiers(&self, mode: Mode) {
+	/// Records the user's poses to replace their gesture thresholds.
+	pub fn start_calibration(&mut self, profile: String) {
 		match self {
-			Input::Grab(grab_input) => grab_input.update_signifiers(mode),
-			Input::Pen(pen_input) => pen_input.update_signifiers(mode),
+			Input::Grab(_) => {}
+			Input::Pen(pen_input) => pen_input.start_calibration(profile),
+		}
+	}
+	pub async fn update_pen(&mut self) {
+		match self {
+			Input::Grab(_) => {}
+			Input::Pen(pen_input) => pen_input.update_pen().await,
+		}
+	}
+	pub async fn sample_waft(&mut self) -> WaftSample {
+		match self {
+			Input::Grab(grab_input) => grab_input.sample_waft().await,
+			Input::Pen(pen_input) => pen_input.sample_waft().await,
+		}
+	}
+	pub fn gear_shift(&mut self) -> Option<GearShift> {
+		match self {
+			Input::Grab(_) => None,
+			Input::Pen(pen_input) => pen_input.gear_shift(),
+		}
+	}
+	pub fn precision(&self) -> bool {
+		match self {
+			Input::Grab(_) => false,
+			Input::Pen(pen_input) => pen_input.precision(),
+		}
+	}
+	pub fn brake(&mut self) -> bool {
+		match self {
+			Input::Grab(_) => false,
+			Input::Pen(pen_input) => pen_input.brake(),
 		}
 	}
-	pub fn get_velocity_space(&self) -> SpatialRef {
+	pub fn history_action(&mut self) -> Option<HistoryAction> {
 		match self {
-			Input::Grab(grab_input) => grab_input.client.get_root().clone().as_spatial_ref(),
-			Input::Pen(pen_input) => pen_input.client.get_root().clone().as_spatial_ref(),
+			Input::Grab(_) => None,
+			Input::Pen(pen_input) => pen_input.history_action(),
+		}
+	}
+	/// Where to pick an orbit pivot or object to ride around, in the velocity reference space.
+	pub fn pick_request(&mut self) -> Option<Vec3> {
+		match self {
+			Input::Grab(_) => None,
+			Input::Pen(pen_input) => pen_input.pick_request(),
+		}
+	}
+	/// The held pen's tip, in the velocity reference space.
+	pub fn tip(&self) -> Option<Vec3> {
+		match self {
+			Input::Grab(_) => None,
+			Input::Pen(pen_input) => pen_input.tip(),
+		}
+	}
+	pub fn update_signifiers(&mut self, mode: Mode, gears: &Gears, speed: f32, edge: Option<Edge>) {
+		match self {
+			Input::Grab(grab_input) => grab_input.update_signifiers(mode),
+			Input::Pen(pen_input) => pen_input.update_signifiers(mode, gears, speed, edge),
 		}
 	}
 }
 impl PenInput {
 	const LENGTH: f32 = 0.075;
 	const THICKNESS: f32 = 0.005;
+	const GEAR_HEIGHT: f32 = Self::LENGTH * 0.75;
+	const DOUBLE_PINCH: Duration = Duration::from_millis(400);
 	fn update_mode(&mut self) -> bool {
 		if !self.button.handle_events() {
 			return false;
 		}
 		self.button.released()
 	}
-	async fn new(client: &Arc<ClientHandle>, connection: Connection) -> NodeResult<Self> {
+	fn gear_shift(&mut self) -> Option<GearShift> {
+		if self.gear_button.handle_events() && self.gear_button.released() {
+			return Some(GearShift::Cycle);
+		}
+		self.gear_shift.take()
+	}
+	/// Held by making a fist with the hand (or squeezing the controller) not holding the pen.
+	fn precision(&self) -> bool {
+		self.grab_action.actor().is_some() && !self.precision_action.currently_acting().is_empty()
+	}
+	/// An open palm held up like a stop sign, or a controller's context button, held briefly.
+	fn brake(&mut self) -> bool {
+		Self::held_for(
+			&self.brake_action,
+			&mut self.brake_held,
+			Duration::from_millis(150),
+		)
+	}
+	/// Undo on the left side button, redo on the right.
+	fn history_action(&mut self) -> Option<HistoryAction> {
+		if self.undo_button.handle_events() && self.undo_button.released() {
+			return Some(HistoryAction::Undo);
+		}
+		if self.redo_button.handle_events() && self.redo_button.released() {
+			return Some(HistoryAction::Redo);
+		}
+		None
+	}
+	/// The pen tip after a double pinch on the held pen.
+	fn pick_request(&mut self) -> Option<Vec3> {
+		if !std::mem::take(&mut self.pick_requested) {
+			return None;
+		}
+		self.tip()
+	}
+	fn tip(&self) -> Option<Vec3> {
+		Some(self.handler_to_root?.transform_point3(self.grab_position?))
+	}
+	/// True once per gesture, after `action` has been acting for `hold`.
+	fn held_for(action: &SimpleAction, held: &mut Option<(Instant, bool)>, hold: Duration) -> bool {
+		if action.currently_acting().is_empty() {
+			*held = None;
+			return false;
+		}
+		let (since, fired) = held.get_or_insert((Instant::now(), false));
+		if *fired || since.elapsed() < hold {
+			return false;
+		}
+		*fired = true;
+		true
+	}
+	fn is_stop_pose(hand: &Hand) -> bool {
+		let palm = Vec3::from(hand.palm.position);
+		let extended = [&hand.index, &hand.middle, &hand.ring, &hand.little]
+			.iter()
+			.all(|finger| Vec3::from(finger.tip.position).distance(palm) > 0.07);
+		// -Z on the palm joint points along the fingers
+		let fingers_up = (Quat::from(hand.palm.rotation) * Vec3::NEG_Z).y > 0.7;
+		extended && fingers_up
+	}
+	fn is_palm_up(hand: &Hand) -> bool {
+		// +Y on the palm joint points out of the back of the hand
+		(Quat::from(hand.palm.rotation) * Vec3::Y).y < -0.7
+	}
+	async fn new(
+		client: &Arc<ClientHandle>,
+		connection: Connection,
+		settings: &Settings,
+	) -> NodeResult<Self> {
 		let pen_root = Spatial::create(client.get_root(), Transform::none())?;
 		let signifiers = Lines::create(&pen_root, Transform::none(), &[])?;
 		let field = Field::create(
@@ -151,6 +338,26 @@ impl PenInput {
 			[0.02; 2],
 			ButtonSettings::default(),
 		)?;
+		let gear_button = Button::create(
+			&pen_root,
+			Transform::from_translation([0.0, Self::GEAR_HEIGHT, Self::THICKNESS]),
+			[0.01; 2],
+			ButtonSettings::default(),
+		)?;
+		// on either side of the pen, facing out
+		let side_button = |side: f32| {
+			Button::create(
+				&pen_root,
+				Transform::from_translation_rotation(
+					[side * Self::THICKNESS, Self::LENGTH * 0.5, 0.0],
+					Quat::from_rotation_y(side * FRAC_PI_2),
+				),
+				[0.01; 2],
+				ButtonSettings::default(),
+			)
+		};
+		let undo_button = side_button(-1.0)?;
+		let redo_button = side_button(1.0)?;
 		let button_model = Model::create(
 			button.touch_plane().root(),
 			Transform::identity(),
@@ -163,20 +370,81 @@ impl PenInput {
 			field.clone().as_spatial(),
 			Some(field.clone()),
 		)?;
+		let theme = Theme::load(&settings.theme);
+		let model = PenModel::create(&pen_root, &theme)?;
+		let readout = Readout::create(
+			&pen_root,
+			Transform::from_translation([0.02, Self::LENGTH * 1.1, 0.0]),
+		)?;
+		let dock = match settings.pen_dock {
+			Some(dock) => Some(
+				PenDock::new(
+					client,
+					dock,
+					queue.handler().clone().as_spatial().as_spatial_ref(),
+				)
+				.await?,
+			),
+			None => None,
+		};
+		let (summon_sender, summon_requests) = mpsc::unbounded_channel();
+		if let Err(err) = listen_for_summons(&connection, summon_sender).await {
+			error!("unable to listen for pen summons: {err}");
+		}
+		let hmd = hmd(client).await;
+		let head_to_root = match (settings.steering, &hmd) {
+			(Steering::Gaze { .. }, Some(hmd)) => Some(PipelinedTransform::new(
+				hmd.clone(),
+				client.get_root().clone().as_spatial_ref(),
+			)),
+			_ => None,
+		};
+		let head_to_handler = hmd.clone().map(|hmd| {
+			PipelinedTransform::new(hmd, queue.handler().clone().as_spatial().as_spatial_ref())
+		});
 		let mut pen = Self {
 			move_action: Default::default(),
 			grab_action: Default::default(),
+			accessibility: settings.accessibility,
+			steering: settings.steering,
+			head_to_root,
+			thrust_latched: false,
+			precision_action: Default::default(),
+			brake_action: Default::default(),
+			brake_held: None,
+			last_pinch: None,
+			pick_requested: false,
+			tracked_action: Default::default(),
+			handler_to_root: None,
+			thresholds: Thresholds::load(&settings.profile),
+			theme,
+			calibration: None,
+			dock,
+			grab_position: None,
+			summon_action: Default::default(),
+			summon_held: None,
+			summon_requests,
+			summon: None,
+			hmd,
+			head_to_handler,
 			field,
 			pen_root,
 			queue,
-			prev_position: None,
 			signifiers,
 			client: client.clone(),
 			button,
+			gear_button,
+			undo_button,
+			redo_button,
+			gear_scrolled: false,
+			gear_shift: None,
 			reparentable: None,
 			connection,
 			derezzable,
+			derezzed: false,
 
+			model,
+			readout,
 			_button_model: button_model,
 		};
 		pen.make_reparentable();
@@ -198,38 +466,141 @@ impl PenInput {
 	}
 	fn handle_input(&mut self) {
 		if let Ok(_) = self.derezzable.receiver.try_recv() {
-			process::exit(0);
+			self.derezzed = true;
+			return;
+		}
+		if self.summon_requests.try_recv().is_ok() {
+			self.start_summon(SummonTarget::Head);
 		}
 		if !self.queue.handle_events() {
 			return;
 		}
+		// a docked pen is out of sight, so be more forgiving when reaching for it
+		let grab_reach = match self.dock.as_ref().is_some_and(PenDock::is_docked) {
+			true => 0.1,
+			false => 0.05,
+		};
+		let thresholds = self.thresholds;
+		let was_grabbing = self.grab_action.actor().cloned();
 		self.grab_action.update(
 			false,
 			&self.queue,
-			|data| data.distance < 0.05,
-			|data| {
-				data.datamap.with_data(|datamap| match &data.input {
-					InputDataType::Hand(_) => datamap.idx("grab_strength").as_f32() > 0.80,
-					InputDataType::Tip(_) => datamap.idx("grab").as_f32() > 0.90,
-					_ => false,
-				})
-			},
+			|data| data.distance < grab_reach,
+			|data| thresholds.grabbing(data, was_grabbing.as_deref() == Some(data)),
 		);
+		let was_pinching = self.move_action.currently_acting().clone();
 		self.move_action.update(&self.queue, &|data| {
-			data.datamap.with_data(|datamap| match &data.input {
+			thresholds.pinching(data, was_pinching.contains(data))
+		});
+
+		let grab_actor = self.grab_action.actor().cloned();
+		let was_precise = self.precision_action.currently_acting().clone();
+		self.precision_action.update(&self.queue, &|data| {
+			grab_actor.as_deref() != Some(data)
+				&& thresholds.grabbing(data, was_precise.contains(data))
+		});
+		let was_braking = self.brake_action.currently_acting().clone();
+		self.brake_action
+			.update(&self.queue, &|data| match &data.input {
 				InputDataType::Hand(h) => {
-					Vec3::from(h.thumb.tip.position).distance(h.index.tip.position.into()) < 0.03
+					thresholds.open_hand(data, was_braking.contains(data)) && Self::is_stop_pose(h)
 				}
-				InputDataType::Tip(_) => datamap.idx("select").as_f32() > 0.01,
+				InputDataType::Tip(_) => data
+					.datamap
+					.with_data(|datamap| datamap.idx("context").as_f32() > 0.5),
 				_ => false,
-			})
+			});
+		if let Some(grab_actor) = &grab_actor
+			&& let InputDataType::Tip(_) = &grab_actor.input
+		{
+			let scroll = grab_actor
+				.datamap
+				.with_data(|datamap| datamap.idx("scroll").as_vector().idx(1).as_f32());
+			if !self.gear_scrolled && scroll.abs() > 0.7 {
+				self.gear_shift = Some(match scroll > 0.0 {
+					true => GearShift::Up,
+					false => GearShift::Down,
+				});
+			}
+			self.gear_scrolled = scroll.abs() > 0.3;
+		}
+
+		let was_summoning = self.summon_action.currently_acting().clone();
+		self.summon_action.update(&self.queue, &|data| {
+			grab_actor.as_deref() != Some(data)
+				&& matches!(&data.input, InputDataType::Hand(h) if Self::is_palm_up(h))
+				&& thresholds.summon_pinching(data, was_summoning.contains(data))
 		});
+		if Self::held_for(
+			&self.summon_action,
+			&mut self.summon_held,
+			Duration::from_secs(1),
+		) && let Some(InputDataType::Hand(h)) = self
+			.summon_action
+			.currently_acting()
+			.iter()
+			.next()
+			.map(|data| &data.input)
+		{
+			let above_palm = Vec3::from(h.palm.position) + Vec3::Y * 0.05;
+			self.start_summon(SummonTarget::Point(above_palm));
+		}
+
+		self.tracked_action.update(&self.queue, &|data| {
+			!matches!(data.input, InputDataType::Pointer(_))
+		});
+		if let Some(calibration) = &mut self.calibration
+			&& let Some(thresholds) = calibration.update(
+				self.tracked_action
+					.currently_acting()
+					.iter()
+					.map(|data| &**data),
+			) {
+			self.thresholds = thresholds;
+			self.calibration = None;
+		}
+		if let Some(dock) = &mut self.dock {
+			let wrist = self
+				.tracked_action
+				.currently_acting()
+				.iter()
+				.find_map(|data| match &data.input {
+					InputDataType::Hand(h) => dock.anchor().pose_on_hand(h),
+					_ => None,
+				});
+			if let Some(wrist) = wrist {
+				dock.set_pose(self.queue.handler(), wrist);
+			}
+		}
 
 		if self.grab_action.actor_started() {
+			self.summon.take();
 			self.reparentable.take();
+			if let Some(dock) = &mut self.dock {
+				dock.undock(&self.pen_root, self.client.get_root());
+			}
+		}
+		if let Some(grab_actor) = &grab_actor
+			&& self.move_action.started_acting().contains(grab_actor)
+		{
+			if self.accessibility.latch_thrust {
+				self.thrust_latched = !self.thrust_latched;
+			}
+			let now = Instant::now();
+			match self.last_pinch.take() {
+				Some(last) if now - last < Self::DOUBLE_PINCH => self.pick_requested = true,
+				_ => self.last_pinch = Some(now),
+			}
 		}
 		if self.grab_action.actor_stopped() {
-			self.make_reparentable();
+			self.thrust_latched = false;
+			let docked = match (&mut self.dock, self.grab_position.take()) {
+				(Some(dock), Some(position)) => dock.try_dock(&self.pen_root, position),
+				_ => false,
+			};
+			if !docked {
+				self.make_reparentable();
+			}
 		}
 		let Some(grab_actor) = self.grab_action.actor() else {
 			return;
@@ -245,75 +616,247 @@ impl PenInput {
 			),
 			_ => Transform::none(),
 		};
+		self.grab_position = transform.translation.map(Vec3::from);
 		let _ = self
 			.pen_root
 			.set_relative_transform(self.queue.handler(), transform);
 	}
-	pub async fn waft(&mut self, _delta_secs: f32) -> Vec3 {
+	fn start_calibration(&mut self, profile: String) {
+		let wizard = match &self.hmd {
+			Some(hmd) => CalibrationWizard::new(hmd, profile),
+			None => CalibrationWizard::new(&self.pen_root, profile),
+		};
+		self.calibration = wizard
+			.inspect_err(|err| error!("unable to start calibration: {err}"))
+			.ok();
+	}
+	fn start_summon(&mut self, target: SummonTarget) {
+		if self.grab_action.actor().is_some()
+			|| (target == SummonTarget::Head && self.hmd.is_none())
+		{
+			return;
+		}
+		self.reparentable.take();
+		if let Some(dock) = &mut self.dock {
+			dock.undock(&self.pen_root, self.client.get_root());
+		}
+		self.summon = Some(Summon::new(target));
+	}
+	async fn update_pen(&mut self) {
+		if let Some(dock) = &mut self.dock {
+			dock.update(self.queue.handler()).await;
+		}
+		self.update_summon().await;
+	}
+	async fn update_summon(&mut self) {
+		let Some(summon) = &mut self.summon else {
+			return;
+		};
+		let handler = self.queue.handler();
+		let start = match summon.start {
+			Some(start) => start,
+			None => match self.pen_root.get_transform(handler).await {
+				Ok(transform) => *summon
+					.start
+					.insert(transform.translation.map(Vec3::from).unwrap_or_default()),
+				Err(err) => {
+					error!("unable to summon pen: {err}");
+					self.summon = None;
+					self.make_reparentable();
+					return;
+				}
+			},
+		};
+		let target = match summon.target {
+			SummonTarget::Point(point) => point,
+			SummonTarget::Head => {
+				let Some(head_to_handler) = &mut self.head_to_handler else {
+					return;
+				};
+				let Ok(head) = head_to_handler.get().await else {
+					return;
+				};
+				head.transform_point3(vec3(0.0, -0.2, -0.3))
+			}
+		};
+		let (position, arrived) = summon.position(start, target);
+		let _ = self.pen_root.set_relative_transform(
+			handler,
+			Transform::from_translation_rotation(position, Quat::IDENTITY),
+		);
+		if arrived {
+			self.summon = None;
+			self.make_reparentable();
+		}
+	}
+	pub async fn sample_waft(&mut self) -> WaftSample {
+		if let Steering::Gaze { lock_pitch } = self.steering {
+			return self.sample_gaze(lock_pitch).await;
+		}
 		let Some(grab_actor) = self.grab_action.actor() else {
-			self.prev_position = None;
-			return Vec3::ZERO;
+			return WaftSample::default();
 		};
 		let position = Vec3::from(match &grab_actor.input {
 			InputDataType::Hand(h) => h.palm.position,
 			InputDataType::Tip(t) => t.origin,
 			_ => unreachable!(),
 		});
-		let handler_spatial = self.queue.handler().clone().as_spatial();
-
-		let root_transform = handler_spatial
-			.get_transform(self.client.get_root())
-			.await
-			.unwrap();
-		let mat = mat_from_transform(&root_transform);
-		let position = mat.transform_point3(position);
-		if self.move_action.currently_acting().contains(grab_actor)
-			&& let Some(prev_position) = self.prev_position
-		{
-			let offset: Vec3 = position - prev_position;
-			let offset_magnify = (offset.length()/* * delta_secs */).powf(0.9);
-			self.prev_position = Some(position);
-			return offset.normalize_or_zero() * offset_magnify;
+		let mat = match self.handler_to_root {
+			Some(mat) => mat,
+			// the handler never moves, so this only needs asking once
+			None => match self
+				.queue
+				.handler()
+				.get_transform(self.client.get_root())
+				.await
+			{
+				Ok(transform) => *self.handler_to_root.insert(mat_from_transform(&transform)),
+				Err(err) => {
+					error!("unable to get input handler transform: {err}");
+					return WaftSample::default();
+				}
+			},
+		};
+		WaftSample {
+			position: Some(mat.transform_point3(position)),
+			thrusting: self.thrusting(grab_actor),
+			pointing: match self.steering {
+				Steering::Waft | Steering::Gaze { .. } => None,
+				Steering::Point => Some(Pointing {
+					direction: mat
+						.transform_vector3(Self::pointing_direction(grab_actor))
+						.normalize_or_zero(),
+					throttle: self.throttle(grab_actor),
+				}),
+			},
 		}
-
-		self.prev_position = Some(position);
-
-		Vec3::ZERO
 	}
-	pub fn update_signifiers(&self, mode: Mode) {
-		let thickness = Self::THICKNESS * 0.5;
-		let visual_length = Self::LENGTH;
-		let grabbing = self
-			.grab_action
-			.actor()
-			.is_some_and(|actor| self.move_action.currently_acting().contains(actor));
-		let color = match (mode, grabbing) {
-			(Mode::Reparent, false) => rgba!(0.015686, 0.992157, 0.298039, 1.0).to_linear(),
-			(Mode::MonadoOffset, false) => rgba!(0.361, 0.161, 0.514, 1.0).to_linear(),
-			(Mode::Disabled, _) => rgba_linear!(0.033104762, 0.033104762, 0.033104762, 1.),
-			(_, true) => rgba_linear!(0., 0.26223028, 1., 1.),
+	/// Along the tip, or the index finger for hands, relative to the input handler.
+	fn pointing_direction(actor: &InputData) -> Vec3 {
+		match &actor.input {
+			InputDataType::Hand(h) => {
+				Vec3::from(h.index.tip.position) - Vec3::from(h.index.proximal.position)
+			}
+			InputDataType::Tip(t) => Quat::from(t.orientation) * Vec3::NEG_Z,
+			_ => Vec3::ZERO,
+		}
+	}
+	/// How hard `actor` is pinching or pulling the trigger, past a small dead zone.
+	fn throttle(&self, actor: &Arc<InputData>) -> f32 {
+		if self.accessibility.one_handed || self.accessibility.latch_thrust {
+			return match self.thrusting(actor) {
+				true => 1.0,
+				false => 0.0,
+			};
+		}
+		let strength = actor.datamap.with_data(|datamap| match &actor.input {
+			InputDataType::Hand(_) => datamap.idx("pinch_strength").as_f32(),
+			InputDataType::Tip(_) => datamap.idx("select").as_f32(),
+			_ => 0.0,
+		});
+		Self::dead_zone(strength)
+	}
+	fn dead_zone(value: f32) -> f32 {
+		value.signum() * ((value.abs() - 0.1) / 0.9).clamp(0.0, 1.0)
+	}
+	/// Heads where the user looks, whether or not the pen is held.
+	async fn sample_gaze(&mut self, lock_pitch: bool) -> WaftSample {
+		let Some(head_to_root) = &mut self.head_to_root else {
+			return WaftSample::default();
 		};
-		let signifier_lines = [Line {
-			points: vec![
-				LinePoint {
-					point: [0.0; 3].into(),
-					thickness: 0.0,
-					color,
-				},
-				LinePoint {
-					point: [0.0, thickness, 0.0].into(),
-					thickness,
-					color,
-				},
-				LinePoint {
-					point: [0.0, visual_length, 0.0].into(),
-					thickness,
-					color,
-				},
-			],
-			cyclic: false,
-		}];
-		self.signifiers.set_lines(&signifier_lines).unwrap();
+		let head = match head_to_root.get().await {
+			Ok(head) => head,
+			Err(err) => {
+				error!("unable to get head transform: {err}");
+				return WaftSample::default();
+			}
+		};
+		let mut direction = head.transform_vector3(Vec3::NEG_Z);
+		if lock_pitch {
+			direction.y = 0.0;
+		}
+		let throttle = self.gaze_throttle();
+		WaftSample {
+			// the head stands in for the hand that orbit mode turns around the pivot from
+			position: Some(head.translation.into()),
+			thrusting: throttle != 0.0,
+			pointing: Some(Pointing {
+				direction: direction.normalize_or_zero(),
+				throttle,
+			}),
+		}
+	}
+	/// The strongest pinch, trigger or thumbstick push from any hand or controller.
+	fn gaze_throttle(&self) -> f32 {
+		let summoning = self.summon_action.currently_acting();
+		let grab_actor = self.grab_action.actor();
+		self.tracked_action
+			.currently_acting()
+			.iter()
+			.filter(|data| !summoning.contains(*data))
+			.map(|data| {
+				data.datamap.with_data(|datamap| match &data.input {
+					InputDataType::Hand(_) => {
+						Self::dead_zone(datamap.idx("pinch_strength").as_f32())
+					}
+					InputDataType::Tip(_) => {
+						let select = Self::dead_zone(datamap.idx("select").as_f32());
+						// the thumbstick of the controller holding the pen shifts gears
+						let stick = match grab_actor == Some(data) {
+							true => 0.0,
+							false => {
+								Self::dead_zone(datamap.idx("scroll").as_vector().idx(1).as_f32())
+							}
+						};
+						match select > stick.abs() {
+							true => select,
+							false => stick,
+						}
+					}
+					_ => 0.0,
+				})
+			})
+			.max_by(|a, b| a.abs().total_cmp(&b.abs()))
+			.unwrap_or(0.0)
+	}
+	/// Whether `actor`, holding the pen, is thrusting.
+	fn thrusting(&self, actor: &Arc<InputData>) -> bool {
+		if self.accessibility.one_handed {
+			true
+		} else if self.accessibility.latch_thrust {
+			self.thrust_latched
+		} else {
+			self.move_action.currently_acting().contains(actor)
+		}
+	}
+	pub fn update_signifiers(&mut self, mode: Mode, gears: &Gears, speed: f32, edge: Option<Edge>) {
+		let held = self.grab_action.actor();
+		self.readout
+			.update(held.is_some(), mode, gears, speed, edge);
+		self.model.update(PenState {
+			mode,
+			held: held.is_some(),
+			thrusting: held.is_some_and(|actor| self.thrusting(actor)),
+			precision: gears.precision(),
+			speed,
+		});
+		// one ring around the pen per gear, up to the current one, styled by mode
+		let stroke = self.theme.mode(mode);
+		let gear_rings = (0..=gears.current())
+			.flat_map(|gear| {
+				stroke.draw(
+					circle(32, 0.0, Self::THICKNESS * 0.75).transform(
+						Mat4::from_translation(vec3(
+							0.0,
+							Self::GEAR_HEIGHT - gear as f32 * 0.004,
+							0.0,
+						)) * Mat4::from_rotation_x(FRAC_PI_2),
+					),
+					0.001,
+				)
+			})
+			.collect::<Vec<_>>();
+		self.signifiers.set_lines(&gear_rings).unwrap();
 	}
 }
 impl GrabInput {
@@ -323,45 +866,35 @@ impl GrabInput {
 	}
 	pub fn handle_input(&mut self) {
 		self.queue.handle_events();
+		let thresholds = self.thresholds;
+		let was_grabbing = self.move_action.actor().cloned();
 		self.move_action.update(
 			true,
 			&self.queue,
 			|data| !matches!(&data.input, InputDataType::Pointer(_)),
-			|data| {
-				data.datamap.with_data(|d| match &data.input {
-					InputDataType::Hand(_) => d.idx("grab_strength").as_f32() > 0.9,
-					_ => d.idx("grab").as_f32() > 0.9,
-				})
-			},
+			|data| thresholds.grabbing(data, was_grabbing.as_deref() == Some(data)),
 		);
 	}
-	pub async fn waft(&mut self, _delta_secs: f32) -> Vec3 {
-		let position = self.move_action.actor().map(|p| match &p.input {
-			InputDataType::Hand(h) => h.palm.position.into(),
-			InputDataType::Tip(t) => t.origin.into(),
+	pub async fn sample_waft(&mut self) -> WaftSample {
+		let Some(position) = self.move_action.actor().map(|p| match &p.input {
+			InputDataType::Hand(h) => Vec3::from(h.palm.position),
+			InputDataType::Tip(t) => Vec3::from(t.origin),
 			_ => unreachable!(),
-		});
-
-		if let Some(prev_position) = self.prev_position
-			&& let Some(position) = position
-		{
-			let handler_spatial = self.queue.handler().clone().as_spatial();
-
-			let root_transform = handler_spatial
-				.get_transform(self.client.get_root())
-				.await
-				.unwrap();
-			let mat = mat_from_transform(&root_transform);
-			let position = mat.transform_point3(position);
-
-			let offset: Vec3 = position - prev_position;
-			let offset_magnify = (offset.length()/* * delta_secs */).powf(0.9);
-			self.prev_position = Some(position);
-			return offset.normalize_or_zero() * offset_magnify;
+		}) else {
+			return WaftSample::default();
+		};
+		let mat = match self.handler_to_root.get().await {
+			Ok(mat) => mat,
+			Err(err) => {
+				error!("unable to get input handler transform: {err}");
+				return WaftSample::default();
+			}
+		};
+		WaftSample {
+			position: Some(mat.transform_point3(position)),
+			thrusting: true,
+			pointing: None,
 		}
-
-		self.prev_position = position;
-		Vec3::ZERO
 	}
 	pub fn update_signifiers(&self, mode: Mode) {
 		if matches!(mode, Mode::Disabled) {
@@ -373,16 +906,17 @@ impl GrabInput {
 			.hovering()
 			.current()
 			.iter()
-			.map(|input| self.generate_signifier(input, false, mode))
+			.flat_map(|input| self.generate_signifier(input, false, mode))
 			.collect::<Vec<_>>();
 		signifier_lines.extend(
 			self.move_action
 				.actor()
-				.map(|input| self.generate_signifier(input, true, mode)),
+				.into_iter()
+				.flat_map(|input| self.generate_signifier(input, true, mode)),
 		);
 		self.signifiers.set_lines(&signifier_lines).unwrap();
 	}
-	fn generate_signifier(&self, input: &InputData, grabbing: bool, mode: Mode) -> Line {
+	fn generate_signifier(&self, input: &InputData, grabbing: bool, mode: Mode) -> Vec<Line> {
 		let transform = match &input.input {
 			InputDataType::Pointer(_) => panic!("awawawawawawa"),
 			InputDataType::Hand(h) => {
@@ -395,22 +929,15 @@ impl GrabInput {
 			}
 		};
 
-		let line = circle(
-			64,
-			0.0,
-			match &input.input {
-				InputDataType::Pointer(_) => panic!("awawawawawawa"),
-				InputDataType::Hand(_) => 0.1,
-				InputDataType::Tip(_) => 0.0025,
-			},
-		)
-		.transform(transform);
-		if grabbing {
-			line.color(rgba_linear!(0., 0.26223028, 1., 1.))
-		} else if matches!(mode, Mode::MonadoOffset) {
-			line.color(rgba_linear!(1.0, 1.0, 0.0, 1.0))
-		} else {
-			line
-		}
+		let (radius, thickness) = match &input.input {
+			InputDataType::Pointer(_) => panic!("awawawawawawa"),
+			InputDataType::Hand(_) => (0.1, 0.002),
+			InputDataType::Tip(_) => (0.0025, 0.0005),
+		};
+		let stroke = match grabbing {
+			true => self.theme.grabbing,
+			false => self.theme.mode(mode),
+		};
+		stroke.draw(circle(64, 0.0, radius).transform(transform), thickness)
 	}
 }
@@ -0,0 +1,1277 @@
+use std::fmt;
+
+use glam::{Affine3A, Vec3};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+	backend::{OriginPose, SpatialTree, TrackingOrigin, TrackingOriginStore},
+	bounds::{Bounds, Edge},
+	history::{Flight, History, HistoryAction, Placement, Segment},
+	monado_movement::MonadoMovement,
+	orbit::Orbit,
+	reparentable_movement::ReparentMovement,
+};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode {
+	Reparent,
+	MonadoOffset,
+	/// Monado offsets with gravity and a floor.
+	Walk,
+	/// Monado offsets turning around a picked pivot.
+	Orbit,
+	/// Like reparent, but only moving the objects inside a placed zone.
+	Zone,
+	Disabled,
+}
+impl Mode {
+	/// The mode after this one in the mode switch cycle, skipping modes without Monado or a
+	/// zone to move.
+	pub fn next(self, monado: bool, zone: bool) -> Mode {
+		let mut mode = self;
+		loop {
+			mode = match mode {
+				Mode::Reparent => Mode::MonadoOffset,
+				Mode::MonadoOffset => Mode::Walk,
+				Mode::Walk => Mode::Orbit,
+				Mode::Orbit => Mode::Zone,
+				Mode::Zone => Mode::Reparent,
+				Mode::Disabled => Mode::MonadoOffset,
+			};
+			let available = match mode {
+				Mode::MonadoOffset | Mode::Walk | Mode::Orbit => monado,
+				Mode::Zone => zone,
+				Mode::Reparent | Mode::Disabled => true,
+			};
+			if available {
+				return mode;
+			}
+		}
+	}
+}
+impl fmt::Display for Mode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Mode::Reparent => "Reparent",
+			Mode::MonadoOffset => "Offset",
+			Mode::Walk => "Walk",
+			Mode::Orbit => "Orbit",
+			Mode::Zone => "Zone",
+			Mode::Disabled => "Disabled",
+		})
+	}
+}
+
+/// A single frame of the grabbing hand or tip, in the velocity reference space.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WaftSample {
+	/// Grabbing palm or tip, or the head when steering by gaze.
+	pub position: Option<Vec3>,
+	pub thrusting: bool,
+	/// Set when steering by pointing, which replaces wafting.
+	pub pointing: Option<Pointing>,
+}
+
+/// Where the pen points and how hard it's pinched, to fly that way instead of wafting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pointing {
+	/// Unit direction to fly toward, in the velocity reference space.
+	pub direction: Vec3,
+	/// From 0 to 1, or down to -1 to back up.
+	pub throttle: f32,
+}
+impl Pointing {
+	/// Velocity gained per second at full throttle, before gears.
+	const ACCELERATION: f32 = 2.7;
+
+	pub fn thrust(&self, delta_secs: f32) -> Vec3 {
+		// velocity is how the world moves past us, so it's against the heading
+		-self.direction * self.throttle.clamp(-1.0, 1.0) * Self::ACCELERATION * delta_secs
+	}
+}
+
+/// Turns hand movement between frames into thrust.
+#[derive(Debug, Default)]
+pub struct Waft {
+	prev_position: Option<Vec3>,
+}
+impl Waft {
+	pub fn thrust(&mut self, sample: WaftSample) -> Vec3 {
+		let Some(position) = sample.position else {
+			self.prev_position = None;
+			return Vec3::ZERO;
+		};
+		match self.prev_position.replace(position) {
+			Some(prev_position) if sample.thrusting => {
+				let offset: Vec3 = position - prev_position;
+				let offset_magnify = (offset.length()/* * delta_secs */).powf(0.9);
+				offset.normalize_or_zero() * offset_magnify
+			}
+			_ => Vec3::ZERO,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GearShift {
+	Up,
+	Down,
+	/// Up, wrapping around to the lowest gear.
+	Cycle,
+}
+
+/// Thrust multipliers to pick from, plus a held precision modifier.
+#[derive(Debug, Clone)]
+pub struct Gears {
+	multipliers: Vec<f32>,
+	current: usize,
+	precision_multiplier: f32,
+	precision: bool,
+}
+impl Default for Gears {
+	fn default() -> Self {
+		Gears::new(vec![0.25, 1.0, 4.0, 16.0], 1, 0.2)
+	}
+}
+impl Gears {
+	pub fn new(multipliers: Vec<f32>, current: usize, precision_multiplier: f32) -> Self {
+		let multipliers = if multipliers.is_empty() {
+			vec![1.0]
+		} else {
+			multipliers
+		};
+		Gears {
+			current: current.min(multipliers.len() - 1),
+			multipliers,
+			precision_multiplier,
+			precision: false,
+		}
+	}
+	pub fn shift(&mut self, shift: GearShift) {
+		let top = self.multipliers.len() - 1;
+		self.current = match shift {
+			GearShift::Up => (self.current + 1).min(top),
+			GearShift::Down => self.current.saturating_sub(1),
+			GearShift::Cycle if self.current == top => 0,
+			GearShift::Cycle => self.current + 1,
+		};
+	}
+	pub fn set_gear(&mut self, gear: usize) {
+		self.current = gear.min(self.multipliers.len() - 1);
+	}
+	pub fn set_precision(&mut self, precision: bool) {
+		self.precision = precision;
+	}
+	pub fn current(&self) -> usize {
+		self.current
+	}
+	pub fn precision(&self) -> bool {
+		self.precision
+	}
+	/// Thrust multiplier of every gear, lowest first.
+	pub fn multipliers(&self) -> &[f32] {
+		&self.multipliers
+	}
+	pub fn precision_multiplier(&self) -> f32 {
+		self.precision_multiplier
+	}
+	pub fn multiplier(&self) -> f32 {
+		let precision = match self.precision {
+			true => self.precision_multiplier,
+			false => 1.0,
+		};
+		self.multipliers[self.current] * precision
+	}
+}
+
+/// Vertical motion while walking, in stage space.
+#[derive(Debug, Default)]
+pub struct Gravity {
+	fall_speed: f32,
+	grounded: bool,
+}
+impl Gravity {
+	/// Floor height of the play space in stage space.
+	pub const FLOOR: f32 = 0.0;
+	const ACCELERATION: f32 = 9.81;
+	const JUMP_SPEED: f32 = 2.5;
+	/// Upward thrust in a single frame needed to jump, about a quick downward push of the hand.
+	const JUMP_THRUST: f32 = 0.02;
+
+	pub fn is_grounded(&self) -> bool {
+		self.grounded
+	}
+
+	/// Returns how far the feet should move up this frame, never taking them below the floor.
+	pub fn step(&mut self, delta_secs: f32, feet_height: f32, upward_thrust: f32) -> f32 {
+		if self.grounded && upward_thrust > Self::JUMP_THRUST {
+			self.fall_speed = Self::JUMP_SPEED;
+		}
+		self.fall_speed -= Self::ACCELERATION * delta_secs;
+		let rise = self.fall_speed * delta_secs;
+		self.grounded = feet_height + rise <= Self::FLOOR;
+		if self.grounded {
+			self.fall_speed = 0.0;
+			return Self::FLOOR - feet_height;
+		}
+		rise
+	}
+}
+
+/// Eases velocity down to zero after a brake gesture.
+#[derive(Debug, Clone, Copy)]
+struct Brake {
+	start_velocity: Vec3,
+	elapsed: f32,
+}
+impl Brake {
+	const DURATION: f32 = 0.2;
+}
+
+/// Velocity from wafting, gears and braking, stepped once per frame.
+#[derive(Debug, Default)]
+pub struct Motion {
+	waft: Waft,
+	gears: Gears,
+	thrust: Vec3,
+	velocity: Vec3,
+	brake: Option<Brake>,
+	hand: Option<Vec3>,
+}
+impl Motion {
+	pub fn update_velocity(&mut self, delta_secs: f32, sample: WaftSample) {
+		self.hand = sample.position.or(self.hand);
+		let thrust = match sample.pointing {
+			Some(pointing) => pointing.thrust(delta_secs),
+			None => self.waft.thrust(sample),
+		};
+		self.thrust = thrust * self.gears.multiplier();
+		if let Some(brake) = &mut self.brake {
+			// thrust is ignored until we've stopped
+			self.thrust = Vec3::ZERO;
+			brake.elapsed += delta_secs;
+			let remaining = (1.0 - brake.elapsed / Brake::DURATION).max(0.0);
+			self.velocity = brake.start_velocity * remaining * remaining;
+			if remaining == 0.0 {
+				self.brake = None;
+			}
+			return;
+		}
+		self.velocity *= 0.99;
+		self.velocity += self.thrust;
+	}
+
+	/// Bring velocity to zero over a short ease-out.
+	pub fn brake(&mut self) {
+		if self.velocity != Vec3::ZERO && self.brake.is_none() {
+			self.brake = Some(Brake {
+				start_velocity: self.velocity,
+				elapsed: 0.0,
+			});
+		}
+	}
+
+	pub fn gears(&self) -> &Gears {
+		&self.gears
+	}
+	pub fn gears_mut(&mut self) -> &mut Gears {
+		&mut self.gears
+	}
+	pub fn velocity(&self) -> Vec3 {
+		self.velocity
+	}
+	/// Thrust added this frame.
+	pub fn thrust(&self) -> Vec3 {
+		self.thrust
+	}
+	/// Drops all velocity at once, unlike `brake`.
+	pub fn stop(&mut self) {
+		self.velocity = Vec3::ZERO;
+		self.brake = None;
+	}
+	/// Adds to the velocity, e.g. to keep going after riding along with something.
+	pub fn add_velocity(&mut self, velocity: Vec3) {
+		self.velocity += velocity;
+	}
+	/// Orbit around `pivot` from where the hand last was, once there's been a hand.
+	pub fn orbit(&self, pivot: Vec3) -> Option<Orbit> {
+		self.hand.map(|hand| Orbit { pivot, hand })
+	}
+}
+
+/// What the frame loop wants the movement backend to do next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionTarget {
+	pub mode: Mode,
+	/// Frame time the target covers.
+	pub delta_secs: f32,
+	pub velocity: Vec3,
+	/// Thrust added on the latest frame, for jumping while walking.
+	pub thrust: Vec3,
+	/// Without one, orbit mode moves in straight lines like `MonadoOffset`.
+	pub orbit: Option<Orbit>,
+	/// Latest transform of the spatial being ridden along with, in the velocity reference space.
+	pub ride: Option<Affine3A>,
+}
+impl MotionTarget {
+	pub fn still(mode: Mode) -> Self {
+		MotionTarget {
+			mode,
+			delta_secs: 0.0,
+			velocity: Vec3::ZERO,
+			thrust: Vec3::ZERO,
+			orbit: None,
+			ride: None,
+		}
+	}
+
+	/// `later`, also covering this target's frame time and thrust.
+	pub fn then(self, later: MotionTarget) -> Self {
+		MotionTarget {
+			delta_secs: self.delta_secs + later.delta_secs,
+			thrust: self.thrust + later.thrust,
+			..later
+		}
+	}
+}
+
+/// Moves the user through whichever backend the mode picks.
+pub struct Movement<S, O> {
+	monado_movement: Option<MonadoMovement<O>>,
+	reparent_movement: ReparentMovement<S>,
+	zone_movement: Option<ReparentMovement<S>>,
+	/// Zone mode moves nothing until the zone has been put somewhere.
+	zone_placed: bool,
+	mode: Mode,
+	/// Ridden spatial as of the last step, to follow how far it's moved since.
+	ride: Option<Affine3A>,
+	history: History,
+	/// Where the motion in progress started.
+	segment_start: Option<Placement>,
+	/// Undo or redo in progress, which takes over from any other movement.
+	flight: Option<Flight>,
+}
+
+impl<S: SpatialTree, O: TrackingOriginStore> Movement<S, O> {
+	pub fn new(spatial_tree: S, origins: Option<O>) -> Self {
+		let monado_movement = origins.map(MonadoMovement::new);
+		let mode = match monado_movement.is_some() {
+			true => Mode::MonadoOffset,
+			false => Mode::Reparent,
+		};
+		let mut reparent_movement = ReparentMovement::new(spatial_tree);
+		if mode == Mode::Reparent {
+			reparent_movement.start_session();
+		}
+		Movement {
+			mode,
+			monado_movement,
+			reparent_movement,
+			zone_movement: None,
+			zone_placed: false,
+			ride: None,
+			history: History::default(),
+			segment_start: None,
+			flight: None,
+		}
+	}
+
+	/// Moves `zone` in zone mode, whose objects start out in place wherever it's put.
+	pub fn with_zone(mut self, zone: S) -> Self {
+		self.zone_movement = Some(ReparentMovement::new(zone));
+		self
+	}
+
+	/// Centers the zone on `center` in the velocity reference space, taking in whatever is
+	/// inside it there.
+	pub fn place_zone(&mut self, center: Vec3) {
+		let Some(zone) = self.zone_movement.as_mut() else {
+			return;
+		};
+		// everything in the old spot stays where it is
+		zone.end_session();
+		zone.place(center);
+		self.zone_placed = true;
+		if self.mode == Mode::Zone {
+			zone.start_session();
+		}
+	}
+
+	/// Returns the offset the active movement applied.
+	pub async fn apply_offset(&mut self, target: MotionTarget) -> Vec3 {
+		let MotionTarget {
+			delta_secs,
+			velocity,
+			thrust,
+			orbit,
+			ride,
+			..
+		} = target;
+		if let Some(flight) = &mut self.flight {
+			let (placement, arrived) = flight.step(delta_secs);
+			if arrived {
+				self.flight = None;
+			}
+			if let Some(placement) = placement {
+				self.place(&placement).await;
+			}
+			return Vec3::ZERO;
+		}
+		// keep pushing back in from past the edge even when not moving
+		let outside = self.edge().is_some_and(|edge| edge.distance > 0.0);
+		let fast_enough = velocity.length_squared() > 0.0005 || outside;
+		let moving = fast_enough || ride.is_some();
+		if moving && self.segment_start.is_none() {
+			self.segment_start = self.placement().await;
+		}
+		let ridden = self.ride(delta_secs, ride).await;
+		let moved = match (&self.mode, self.monado_movement.as_mut(), orbit) {
+			// gravity keeps acting after we stop wafting
+			(Mode::Walk, Some(monado), _) => {
+				let velocity = if fast_enough { velocity } else { Vec3::ZERO };
+				monado.walk(delta_secs, velocity, thrust).await
+			}
+			_ if !fast_enough => Vec3::ZERO,
+			(Mode::Orbit, Some(monado), Some(orbit)) => {
+				monado.orbit(delta_secs, velocity, orbit).await
+			}
+			(Mode::MonadoOffset | Mode::Orbit, Some(monado), _) => {
+				monado.apply_offset(delta_secs, velocity).await
+			}
+			(Mode::Reparent, _, _) => {
+				self.reparent_movement
+					.apply_offset(delta_secs, velocity)
+					.await
+			}
+			(Mode::Zone, _, _) => match self.placed_zone() {
+				Some(zone) => zone.apply_offset(delta_secs, velocity).await,
+				None => Vec3::ZERO,
+			},
+			_ => Vec3::ZERO,
+		};
+		if !moving {
+			self.end_segment().await;
+		}
+		ridden + moved
+	}
+
+	/// Flies back along the last motion, or forward along the last undone one.
+	pub async fn history(&mut self, action: HistoryAction) {
+		if self.flight.is_some() {
+			return;
+		}
+		self.end_segment().await;
+		self.ride = None;
+		self.flight = match action {
+			HistoryAction::Undo => self.history.undo(),
+			HistoryAction::Redo => self.history.redo(),
+		};
+	}
+
+	/// Moves the Monado origins named in `selected`, or every one if it's empty, by the
+	/// stage space `correction`, as a step that can be undone like any motion.
+	/// Returns every origin after the correction.
+	pub async fn correct_origins(
+		&mut self,
+		correction: Affine3A,
+		selected: &[String],
+	) -> Option<Vec<TrackingOrigin>> {
+		let Some(monado) = self.monado_movement.as_mut() else {
+			error!("no monado origins to correct");
+			return None;
+		};
+		let (before, after) = monado.correct(correction, selected).await?;
+		self.end_segment().await;
+		self.history.push(Segment {
+			start: Placement::Origins(before),
+			end: Placement::Origins(after.clone()),
+		});
+		Some(after)
+	}
+
+	/// Sets each named Monado origin's offset, then lifts it like the rest. `floor` is how
+	/// far floor leveling had raised them.
+	pub async fn restore_origins(&mut self, offsets: &[(String, OriginPose)], floor: f32) {
+		if let Some(monado) = self.monado_movement.as_mut() {
+			monado.restore(offsets, floor).await;
+		}
+	}
+
+	/// Raises the Monado origins named in `selected` by `correction` like `correct_origins`,
+	/// and walks on the leveled floor from then on.
+	pub async fn level_floor(
+		&mut self,
+		correction: f32,
+		selected: &[String],
+	) -> Option<Vec<TrackingOrigin>> {
+		let after = self
+			.correct_origins(Affine3A::from_translation(Vec3::Y * correction), selected)
+			.await?;
+		if let Some(monado) = self.monado_movement.as_mut() {
+			monado.raise_floor(correction);
+		}
+		Some(after)
+	}
+
+	/// Every Monado origin's current offset, lift included.
+	pub async fn origin_placement(&mut self) -> Option<Vec<TrackingOrigin>> {
+		self.monado_movement.as_mut()?.placement().await
+	}
+
+	pub fn has_monado(&self) -> bool {
+		self.monado_movement.is_some()
+	}
+	pub fn has_zone(&self) -> bool {
+		self.zone_movement.is_some()
+	}
+
+	/// How far the Monado origins are raised for seated use.
+	pub fn lift(&self) -> f32 {
+		self.monado_movement
+			.as_ref()
+			.map_or(0.0, MonadoMovement::lift)
+	}
+	/// How far floor leveling has raised the Monado origins.
+	pub fn floor(&self) -> f32 {
+		self.monado_movement
+			.as_ref()
+			.map_or(0.0, MonadoMovement::floor)
+	}
+
+	/// Where the active backend has put the user.
+	async fn placement(&mut self) -> Option<Placement> {
+		match (self.mode, self.monado_movement.as_mut()) {
+			(Mode::Reparent, _) => self
+				.reparent_movement
+				.placement()
+				.await
+				.map(Placement::World),
+			(Mode::Zone, _) => match self.placed_zone() {
+				Some(zone) => zone.placement().await.map(Placement::Zone),
+				None => None,
+			},
+			(Mode::Disabled, _) | (_, None) => None,
+			(_, Some(monado)) => monado.placement().await.map(Placement::Origins),
+		}
+	}
+
+	async fn place(&mut self, placement: &Placement) {
+		match (placement, self.monado_movement.as_mut()) {
+			(Placement::World(translation), _) => self.reparent_movement.place(*translation),
+			(Placement::Zone(translation), _) => {
+				if let Some(zone) = self.zone_movement.as_mut() {
+					zone.place(*translation);
+				}
+			}
+			(Placement::Origins(origins), Some(monado)) => monado.place(origins).await,
+			(Placement::Origins(_), None) => {}
+		}
+	}
+
+	/// Records the motion in progress, read back from the backend it started in even if
+	/// the mode changed since.
+	async fn end_segment(&mut self) {
+		let Some(start) = self.segment_start.take() else {
+			return;
+		};
+		let end = match (&start, self.monado_movement.as_mut()) {
+			(Placement::World(_), _) => self
+				.reparent_movement
+				.placement()
+				.await
+				.map(Placement::World),
+			(Placement::Zone(_), _) => match self.zone_movement.as_mut() {
+				Some(zone) => zone.placement().await.map(Placement::Zone),
+				None => None,
+			},
+			(Placement::Origins(_), Some(monado)) => {
+				monado.placement().await.map(Placement::Origins)
+			}
+			(Placement::Origins(_), None) => None,
+		};
+		if let Some(end) = end
+			&& end != start
+		{
+			self.history.push(Segment { start, end });
+		}
+	}
+
+	/// Carries the user along with however far the ridden spatial moved since the last step.
+	/// Only Monado can, since in reparent mode the spatial would move along with the world.
+	async fn ride(&mut self, delta_secs: f32, ride: Option<Affine3A>) -> Vec3 {
+		let last = std::mem::replace(&mut self.ride, ride);
+		let (Some(ride), Some(last)) = (ride, last) else {
+			return Vec3::ZERO;
+		};
+		match (self.mode, self.monado_movement.as_mut()) {
+			(Mode::MonadoOffset | Mode::Walk | Mode::Orbit, Some(monado)) => {
+				monado.follow(delta_secs, ride * last.inverse()).await
+			}
+			_ => Vec3::ZERO,
+		}
+	}
+
+	pub fn current_mode(&self) -> Mode {
+		self.mode
+	}
+
+	pub fn switch_mode(&mut self, mode: Mode) {
+		if let (Mode::Walk, Some(monado)) = (mode, self.monado_movement.as_mut()) {
+			monado.start_walking();
+		}
+		// objects only stay on the world root while it's the one being moved
+		match (self.mode == Mode::Reparent, mode == Mode::Reparent) {
+			(false, true) => self.reparent_movement.start_session(),
+			(true, false) => self.reparent_movement.end_session(),
+			_ => {}
+		}
+		if let Some(zone) = self.zone_movement.as_mut()
+			&& self.zone_placed
+		{
+			match (self.mode == Mode::Zone, mode == Mode::Zone) {
+				(false, true) => zone.start_session(),
+				(true, false) => zone.end_session(),
+				_ => {}
+			}
+		}
+		self.mode = mode;
+	}
+
+	fn placed_zone(&mut self) -> Option<&mut ReparentMovement<S>> {
+		self.zone_movement.as_mut().filter(|_| self.zone_placed)
+	}
+
+	/// Raises the user's eyes by `lift` meters, only possible with Monado.
+	pub async fn set_lift(&mut self, lift: f32) {
+		if let Some(monado) = self.monado_movement.as_mut() {
+			monado.set_lift(lift).await;
+		}
+	}
+
+	/// Takes the lift back off before exiting, since Monado keeps the offsets.
+	pub async fn remove_lift(&mut self) {
+		if let Some(monado) = self.monado_movement.as_mut() {
+			monado.remove_lift().await;
+		}
+	}
+
+	pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+		if let Some(monado) = self.monado_movement.as_mut() {
+			monado.set_bounds(bounds);
+		}
+		self.reparent_movement.set_bounds(bounds);
+	}
+
+	/// Closest edge of the bounds for the current mode, normal in velocity space.
+	pub fn edge(&self) -> Option<Edge> {
+		match (self.mode, self.monado_movement.as_ref()) {
+			(Mode::MonadoOffset | Mode::Walk | Mode::Orbit, Some(monado)) => monado.edge(),
+			(Mode::Reparent, _) => self.reparent_movement.edge(),
+			_ => None,
+		}
+	}
+
+	#[cfg(test)]
+	pub fn spatial_tree(&self) -> &S {
+		self.reparent_movement.spatial_tree()
+	}
+
+	#[cfg(test)]
+	pub fn zone_tree(&self) -> Option<&S> {
+		self.zone_movement
+			.as_ref()
+			.map(ReparentMovement::spatial_tree)
+	}
+
+	#[cfg(test)]
+	pub fn origins(&self) -> Option<&O> {
+		self.monado_movement.as_ref().map(MonadoMovement::origins)
+	}
+}
+
+/// Motion and movement stepped in lockstep, for replays and tests.
+pub struct Locomotion<S, O> {
+	motion: Motion,
+	movement: Movement<S, O>,
+	pivot: Option<Vec3>,
+	ride: Option<Affine3A>,
+}
+
+impl<S: SpatialTree, O: TrackingOriginStore> Locomotion<S, O> {
+	pub fn new(spatial_tree: S, origins: Option<O>) -> Self {
+		Locomotion {
+			motion: Motion::default(),
+			movement: Movement::new(spatial_tree, origins),
+			pivot: None,
+			ride: None,
+		}
+	}
+
+	pub fn update_velocity(&mut self, delta_secs: f32, sample: WaftSample) {
+		self.motion.update_velocity(delta_secs, sample);
+	}
+
+	pub fn brake(&mut self) {
+		self.motion.brake();
+	}
+
+	/// Returns the offset the active movement applied this frame.
+	pub async fn apply_offset(&mut self, delta_secs: f32) -> Vec3 {
+		let target = MotionTarget {
+			velocity: self.motion.velocity(),
+			thrust: self.motion.thrust(),
+			orbit: self.pivot.and_then(|pivot| self.motion.orbit(pivot)),
+			ride: self.ride,
+			delta_secs,
+			..MotionTarget::still(self.current_mode())
+		};
+		self.movement.apply_offset(target).await
+	}
+
+	/// Point orbit mode turns around, in the velocity reference space.
+	pub fn set_pivot(&mut self, pivot: Option<Vec3>) {
+		self.pivot = pivot;
+	}
+
+	pub fn current_mode(&self) -> Mode {
+		self.movement.current_mode()
+	}
+
+	pub fn switch_mode(&mut self, mode: Mode) {
+		self.movement.switch_mode(mode);
+	}
+
+	pub fn gears(&self) -> &Gears {
+		self.motion.gears()
+	}
+	pub fn gears_mut(&mut self) -> &mut Gears {
+		self.motion.gears_mut()
+	}
+
+	pub async fn set_lift(&mut self, lift: f32) {
+		self.movement.set_lift(lift).await;
+	}
+
+	#[cfg(test)]
+	pub async fn remove_lift(&mut self) {
+		self.movement.remove_lift().await;
+	}
+
+	pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+		self.movement.set_bounds(bounds);
+	}
+
+	#[cfg(test)]
+	pub fn with_zone(self, zone: S) -> Self {
+		Locomotion {
+			movement: self.movement.with_zone(zone),
+			..self
+		}
+	}
+
+	#[cfg(test)]
+	pub fn place_zone(&mut self, center: Vec3) {
+		self.movement.place_zone(center);
+	}
+
+	#[cfg(test)]
+	pub async fn correct_origins(&mut self, correction: Affine3A, selected: &[String]) {
+		self.movement.correct_origins(correction, selected).await;
+	}
+
+	#[cfg(test)]
+	pub async fn level_floor(&mut self, correction: f32) {
+		self.movement.level_floor(correction, &[]).await;
+	}
+
+	#[cfg(test)]
+	pub fn set_ride(&mut self, ride: Option<Affine3A>) {
+		self.ride = ride;
+	}
+
+	#[cfg(test)]
+	pub async fn history(&mut self, action: HistoryAction) {
+		self.motion.stop();
+		self.movement.history(action).await;
+	}
+
+	pub fn velocity(&self) -> Vec3 {
+		self.motion.velocity()
+	}
+
+	#[cfg(test)]
+	pub fn spatial_tree(&self) -> &S {
+		self.movement.spatial_tree()
+	}
+
+	#[cfg(test)]
+	pub fn zone_tree(&self) -> Option<&S> {
+		self.movement.zone_tree()
+	}
+
+	#[cfg(test)]
+	pub fn origins(&self) -> Option<&O> {
+		self.movement.origins()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Affine3A, Quat, Vec3};
+
+	use super::{GearShift, Locomotion, Mode, Pointing, WaftSample};
+	use crate::{
+		backend::OriginPose,
+		fake_backend::{FakeOrigins, FakeSpatialTree},
+		history::HistoryAction,
+	};
+
+	const FRAME: f32 = 1.0 / 90.0;
+
+	type FakeLocomotion = Locomotion<FakeSpatialTree, FakeOrigins>;
+
+	fn block_on<F: Future>(future: F) -> F::Output {
+		tokio::runtime::Builder::new_current_thread()
+			.build()
+			.unwrap()
+			.block_on(future)
+	}
+
+	/// Starts out in `MonadoOffset` with a single `stage` origin.
+	fn monado() -> FakeLocomotion {
+		monado_with(FakeOrigins::new(&["stage"]))
+	}
+	fn monado_with(origins: FakeOrigins) -> FakeLocomotion {
+		Locomotion::new(FakeSpatialTree::new(), Some(origins))
+	}
+	/// Starts out in `Reparent`, without Monado.
+	fn reparent() -> FakeLocomotion {
+		Locomotion::new(FakeSpatialTree::new(), None)
+	}
+
+	fn stage(locomotion: &FakeLocomotion) -> OriginPose {
+		locomotion.origins().unwrap().offset("stage").unwrap()
+	}
+
+	/// Translation of the world root relative to the velocity reference space.
+	fn world(tree: &FakeSpatialTree) -> Vec3 {
+		tree.relative_transform(tree.world(), tree.velocity_ref())
+			.translation
+			.into()
+	}
+
+	fn pinch(position: Vec3) -> WaftSample {
+		WaftSample {
+			position: Some(position),
+			thrusting: true,
+			..Default::default()
+		}
+	}
+
+	/// One 90 Hz frame of `sample`.
+	async fn step(locomotion: &mut FakeLocomotion, sample: WaftSample) {
+		locomotion.update_velocity(FRAME, sample);
+		locomotion.apply_offset(FRAME).await;
+	}
+
+	/// Moves the hand along +X at `speed` m/s while pinching, for `secs` at 90 Hz.
+	async fn waft(locomotion: &mut FakeLocomotion, speed: f32, secs: f32) {
+		let frames = (secs / FRAME).round() as usize;
+		for frame in 0..=frames {
+			step(locomotion, pinch(Vec3::X * speed * frame as f32 * FRAME)).await;
+		}
+	}
+
+	async fn coast(locomotion: &mut FakeLocomotion, secs: f32) {
+		let frames = (secs / FRAME).round() as usize;
+		for _ in 0..frames {
+			step(locomotion, WaftSample::default()).await;
+		}
+	}
+
+	#[test]
+	fn defaults_to_reparent_without_monado() {
+		assert!(matches!(reparent().current_mode(), Mode::Reparent));
+		assert!(matches!(monado().current_mode(), Mode::MonadoOffset));
+	}
+
+	#[test]
+	fn mode_cycle_skips_missing_backends() {
+		use Mode::*;
+		let cycle = |monado, zone| {
+			let mut modes = vec![Mode::Reparent];
+			while modes.len() < 6 {
+				modes.push(modes.last().unwrap().next(monado, zone));
+			}
+			modes
+		};
+		assert_eq!(
+			cycle(true, true),
+			[Reparent, MonadoOffset, Walk, Orbit, Zone, Reparent]
+		);
+		assert_eq!(
+			cycle(true, false),
+			[Reparent, MonadoOffset, Walk, Orbit, Reparent, MonadoOffset]
+		);
+		assert_eq!(cycle(false, true)[..3], [Reparent, Zone, Reparent]);
+		assert_eq!(cycle(false, false)[..2], [Reparent, Reparent]);
+		assert_eq!(Disabled.next(false, true), Zone);
+	}
+
+	#[test]
+	fn waft_then_coast_moves_origins_against_hand() {
+		block_on(async {
+			let mut locomotion = monado();
+			waft(&mut locomotion, 0.9, 1.0).await;
+			let waft_velocity = locomotion.velocity();
+			assert!(waft_velocity.x > 0.5, "{waft_velocity}");
+			assert!(waft_velocity.y.abs() < f32::EPSILON && waft_velocity.z.abs() < f32::EPSILON);
+			let after_waft = stage(&locomotion);
+			assert!(after_waft.position.x < 0.0);
+
+			coast(&mut locomotion, 1.0).await;
+			assert!(locomotion.velocity().x < waft_velocity.x);
+			let after_coast = stage(&locomotion);
+			assert!(after_coast.position.x < after_waft.position.x);
+
+			coast(&mut locomotion, 10.0).await;
+			let settled = stage(&locomotion);
+			coast(&mut locomotion, 1.0).await;
+			assert_eq!(settled.position, stage(&locomotion).position);
+		});
+	}
+
+	#[test]
+	fn no_thrust_without_pinch() {
+		block_on(async {
+			let mut locomotion = monado();
+			for frame in 0..90 {
+				let sample = WaftSample {
+					thrusting: false,
+					..pinch(Vec3::X * frame as f32 * 0.01)
+				};
+				step(&mut locomotion, sample).await;
+			}
+			assert_eq!(locomotion.velocity(), Vec3::ZERO);
+			assert_eq!(stage(&locomotion).position, Vec3::ZERO);
+		});
+	}
+
+	#[test]
+	fn gears_scale_thrust() {
+		block_on(async {
+			let mut slow = reparent();
+			slow.gears_mut().shift(GearShift::Down);
+			slow.gears_mut().shift(GearShift::Down);
+			assert_eq!(slow.gears().multiplier(), 0.25);
+			let mut fast = reparent();
+			fast.gears_mut().shift(GearShift::Cycle);
+			assert_eq!(fast.gears().multiplier(), 4.0);
+			waft(&mut slow, 0.9, 0.5).await;
+			waft(&mut fast, 0.9, 0.5).await;
+			assert!((fast.velocity().x / slow.velocity().x - 16.0).abs() < 1e-3);
+
+			fast.gears_mut().shift(GearShift::Cycle);
+			fast.gears_mut().shift(GearShift::Cycle);
+			assert_eq!(fast.gears().current(), 0);
+			fast.gears_mut().set_precision(true);
+			assert_eq!(fast.gears().multiplier(), 0.25 * 0.2);
+		});
+	}
+
+	#[test]
+	fn brake_stops_quickly() {
+		block_on(async {
+			let mut locomotion = reparent();
+			waft(&mut locomotion, 0.9, 1.0).await;
+			let start_velocity = locomotion.velocity();
+			locomotion.brake();
+			coast(&mut locomotion, 0.1).await;
+			assert!(locomotion.velocity().x < start_velocity.x * 0.5);
+			assert!(locomotion.velocity().x > 0.0);
+			coast(&mut locomotion, 0.15).await;
+			assert_eq!(locomotion.velocity(), Vec3::ZERO);
+		});
+	}
+
+	#[test]
+	fn monado_offset_follows_velocity_space() {
+		block_on(async {
+			let mut origins = FakeOrigins::new(&["stage"]);
+			origins.velocity_to_stage = Affine3A::from_rotation_y(std::f32::consts::FRAC_PI_2);
+			let mut locomotion = monado_with(origins);
+			waft(&mut locomotion, 0.9, 0.5).await;
+			let offset = stage(&locomotion);
+			assert!(offset.position.z > 0.0);
+			assert!(offset.position.x.abs() < 1e-4);
+		});
+	}
+
+	#[test]
+	fn walking_falls_to_floor_and_jumps() {
+		block_on(async {
+			let mut origins = FakeOrigins::new(&["stage"]);
+			origins.origins[0].offset.position.y = 2.0;
+			let mut locomotion = monado_with(origins);
+			locomotion.switch_mode(Mode::Walk);
+			let feet = |locomotion: &FakeLocomotion| stage(locomotion).position.y;
+
+			coast(&mut locomotion, 2.0).await;
+			assert_eq!(feet(&locomotion), 0.0);
+
+			// wafting sideways walks along the floor
+			for frame in 0..=10 {
+				step(&mut locomotion, pinch(Vec3::X * frame as f32 * 0.005)).await;
+				assert_eq!(feet(&locomotion), 0.0);
+			}
+
+			// a quick downward push jumps
+			locomotion.update_velocity(FRAME, pinch(Vec3::ZERO));
+			step(&mut locomotion, pinch(Vec3::NEG_Y * 0.05)).await;
+			let mut peak = 0.0_f32;
+			for _ in 0..90 {
+				step(&mut locomotion, WaftSample::default()).await;
+				peak = peak.max(feet(&locomotion));
+				assert!(feet(&locomotion) >= 0.0);
+			}
+			assert!(peak > 0.2, "{peak}");
+			assert_eq!(feet(&locomotion), 0.0);
+		});
+	}
+
+	#[test]
+	fn seated_lift_raises_origins_and_floor() {
+		block_on(async {
+			let mut locomotion = monado();
+			locomotion.set_lift(0.5).await;
+			assert_eq!(stage(&locomotion).position.y, 0.5);
+			locomotion.set_lift(0.4).await;
+			assert!((stage(&locomotion).position.y - 0.4).abs() < 1e-6);
+
+			locomotion.switch_mode(Mode::Walk);
+			waft(&mut locomotion, 0.9, 0.5).await;
+			coast(&mut locomotion, 1.0).await;
+			assert!((stage(&locomotion).position.y - 0.4).abs() < 1e-6);
+
+			// keeps wherever walking went, minus the lift
+			let walked = stage(&locomotion).position;
+			locomotion.remove_lift().await;
+			assert!(
+				stage(&locomotion)
+					.position
+					.abs_diff_eq(walked.with_y(0.0), 1e-6)
+			);
+		});
+	}
+
+	#[test]
+	fn orbit_turns_origins_around_pivot() {
+		block_on(async {
+			let mut locomotion = monado();
+			locomotion.switch_mode(Mode::Orbit);
+			locomotion.set_pivot(Some(Vec3::NEG_X));
+			for frame in 0..=45 {
+				step(&mut locomotion, pinch(Vec3::NEG_Z * frame as f32 * 0.01)).await;
+			}
+			let offset = stage(&locomotion);
+			let (axis, angle) = offset.orientation.to_axis_angle();
+			assert!(angle > 0.1 && axis.y.abs() > 0.99, "{axis} {angle}");
+			// turning keeps the origin about as far from the pivot
+			assert!((offset.position.distance(Vec3::NEG_X) - 1.0).abs() < 0.05);
+		});
+	}
+
+	#[test]
+	fn orbit_turns_from_the_head_under_gaze() {
+		block_on(async {
+			let mut locomotion = monado();
+			locomotion.switch_mode(Mode::Orbit);
+			locomotion.set_pivot(Some(Vec3::NEG_X));
+			let gaze = WaftSample {
+				position: Some(Vec3::ZERO),
+				thrusting: true,
+				pointing: Some(Pointing {
+					direction: Vec3::Z,
+					throttle: 1.0,
+				}),
+			};
+			for _ in 0..90 {
+				step(&mut locomotion, gaze).await;
+			}
+			let (axis, angle) = stage(&locomotion).orientation.to_axis_angle();
+			assert!(angle > 0.1 && axis.y.abs() > 0.99, "{axis} {angle}");
+		});
+	}
+
+	#[test]
+	fn pointing_flies_toward_heading() {
+		block_on(async {
+			let pointing = |throttle| WaftSample {
+				pointing: Some(Pointing {
+					direction: Vec3::Z,
+					throttle,
+				}),
+				..Default::default()
+			};
+			let mut locomotion = monado();
+			for _ in 0..90 {
+				step(&mut locomotion, pointing(0.5)).await;
+			}
+			let half = locomotion.velocity();
+			assert!(half.z < 0.0 && half.x == 0.0, "{half}");
+			assert!(stage(&locomotion).position.z > 0.0);
+
+			let mut full = reparent();
+			for _ in 0..90 {
+				full.update_velocity(FRAME, pointing(1.0));
+			}
+			assert!((full.velocity().z / half.z - 2.0).abs() < 1e-3);
+
+			let mut reverse = reparent();
+			reverse.update_velocity(FRAME, pointing(-1.0));
+			assert!(reverse.velocity().z > 0.0);
+		});
+	}
+
+	#[test]
+	fn riding_follows_the_spatial() {
+		block_on(async {
+			let mut locomotion = monado();
+			for frame in 0..=90 {
+				let cart = Affine3A::from_rotation_translation(
+					Quat::from_rotation_y(frame as f32 * 0.01),
+					Vec3::X * frame as f32 * 0.02,
+				);
+				locomotion.set_ride(Some(cart));
+				step(&mut locomotion, WaftSample::default()).await;
+			}
+			let offset = stage(&locomotion);
+			// started at the cart's origin, so stays on it
+			assert!(offset.position.distance(Vec3::X * 1.8) < 1e-4, "{offset:?}");
+			assert!(offset.orientation.angle_between(Quat::from_rotation_y(0.9)) < 1e-4);
+
+			// wafting moves relative to the cart
+			waft(&mut locomotion, 0.9, 0.5).await;
+			assert!(stage(&locomotion).position.x < 1.8);
+		});
+	}
+
+	#[test]
+	fn undo_flies_back_to_where_motion_started() {
+		block_on(async {
+			let mut locomotion = monado();
+			waft(&mut locomotion, 0.9, 0.5).await;
+			coast(&mut locomotion, 10.0).await;
+			let end = stage(&locomotion).position;
+			assert!(end.x < 0.0);
+
+			locomotion.history(HistoryAction::Undo).await;
+			coast(&mut locomotion, 1.0).await;
+			assert_eq!(stage(&locomotion).position, Vec3::ZERO);
+			locomotion.history(HistoryAction::Redo).await;
+			coast(&mut locomotion, 1.0).await;
+			assert_eq!(stage(&locomotion).position, end);
+
+			// the world root in reparent mode
+			let mut locomotion = reparent();
+			waft(&mut locomotion, 0.9, 0.5).await;
+			locomotion.history(HistoryAction::Undo).await;
+			coast(&mut locomotion, 1.0).await;
+			assert!(world(locomotion.spatial_tree()).length() < 1e-5);
+		});
+	}
+
+	#[test]
+	fn zone_moves_only_its_own_root() {
+		block_on(async {
+			let mut locomotion = reparent().with_zone(FakeSpatialTree::new());
+			locomotion.switch_mode(Mode::Zone);
+			assert!(!locomotion.spatial_tree().is_reparenting());
+
+			// nothing to move until the zone is placed
+			waft(&mut locomotion, 0.9, 0.2).await;
+			assert!(!locomotion.zone_tree().unwrap().is_reparenting());
+
+			locomotion.place_zone(Vec3::Z);
+			waft(&mut locomotion, 0.9, 0.5).await;
+			let zone = locomotion.zone_tree().unwrap();
+			assert!(zone.is_reparenting());
+			let root = world(zone);
+			assert!(root.x > 0.0 && (root.z - 1.0).abs() < 1e-5);
+			assert_eq!(world(locomotion.spatial_tree()), Vec3::ZERO);
+
+			// moving the zone starts over with what's inside its new spot
+			locomotion.place_zone(Vec3::NEG_Z);
+			assert_eq!(locomotion.zone_tree().unwrap().reparent_sessions, 2);
+			locomotion.switch_mode(Mode::Reparent);
+			assert!(!locomotion.zone_tree().unwrap().is_reparenting());
+			assert!(locomotion.spatial_tree().is_reparenting());
+		});
+	}
+
+	#[test]
+	fn walking_keeps_the_leveled_floor() {
+		block_on(async {
+			let mut locomotion = monado();
+			locomotion.set_lift(0.3).await;
+			locomotion.level_floor(0.05).await;
+			locomotion.switch_mode(Mode::Walk);
+			waft(&mut locomotion, 0.9, 0.5).await;
+			coast(&mut locomotion, 1.0).await;
+			assert!((stage(&locomotion).position.y - 0.35).abs() < 1e-6);
+		});
+	}
+
+	#[test]
+	fn origin_corrections_can_be_undone() {
+		block_on(async {
+			let mut locomotion = monado_with(FakeOrigins::new(&["stage", "local"]));
+			let height = |locomotion: &FakeLocomotion, name| {
+				locomotion
+					.origins()
+					.unwrap()
+					.offset(name)
+					.unwrap()
+					.position
+					.y
+			};
+			locomotion
+				.correct_origins(
+					Affine3A::from_translation(Vec3::Y * 0.03),
+					&["stage".into()],
+				)
+				.await;
+			assert_eq!(height(&locomotion, "stage"), 0.03);
+			assert_eq!(height(&locomotion, "local"), 0.0);
+
+			locomotion.history(HistoryAction::Undo).await;
+			coast(&mut locomotion, 1.0).await;
+			assert_eq!(height(&locomotion, "stage"), 0.0);
+		});
+	}
+
+	#[test]
+	fn reparent_session_lasts_while_in_reparent_mode() {
+		block_on(async {
+			let mut locomotion = reparent();
+			assert!(locomotion.spatial_tree().is_reparenting());
+			waft(&mut locomotion, 0.9, 1.0).await;
+			assert!(world(locomotion.spatial_tree()).x > 0.0);
+			coast(&mut locomotion, 10.0).await;
+			waft(&mut locomotion, 0.9, 0.2).await;
+			let tree = locomotion.spatial_tree();
+			assert!(tree.is_reparenting());
+			assert_eq!(tree.reparent_sessions, 1);
+
+			let mut locomotion = monado();
+			assert!(!locomotion.spatial_tree().is_reparenting());
+			locomotion.switch_mode(Mode::Reparent);
+			waft(&mut locomotion, 0.9, 0.5).await;
+			assert!(locomotion.spatial_tree().is_reparenting());
+			locomotion.switch_mode(Mode::MonadoOffset);
+			assert!(!locomotion.spatial_tree().is_reparenting());
+		});
+	}
+}
@@ -1,11 +1,36 @@
+mod audio;
+mod backend;
+mod bounds;
+mod calibration;
+mod dock;
+mod fake_backend;
+mod floor;
+mod history;
 mod input;
+mod locomotion;
 mod mode_button;
 mod monado_movement;
+mod movement_task;
+mod orbit;
+mod pen_model;
+mod pipelined_transform;
+mod readout;
+mod recording;
 mod reparentable_movement;
+mod ride;
+mod room;
+mod settings;
 mod solar_sailer;
+mod summon;
+mod targets;
+mod theme;
+mod zone;
+
+use std::path::PathBuf;
 
 use input::Input;
-use solar_sailer::{Mode, SolarSailer};
+use settings::Settings;
+use solar_sailer::SolarSailer;
 use stardust_xr_fusion::{
 	client::Client,
 	objects::object_registry::ObjectRegistry,
@@ -13,12 +38,58 @@ use stardust_xr_fusion::{
 	root::{RootAspect, RootEvent},
 	zbus::{conn::Builder, fdo::ObjectManager},
 };
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{error, warn};
 
 pub const APP_ID: &str = "org.stardustxr.SolarSailer";
 
+#[derive(Default)]
+struct Args {
+	record: Option<PathBuf>,
+	replay: Option<PathBuf>,
+	replay_output: Option<PathBuf>,
+	/// Record gesture thresholds for the settings' profile on startup.
+	calibrate: bool,
+	/// Level the tracked floor with the physical one on startup.
+	level_floor: bool,
+	/// Line the stage up with a wall of the room on startup, after leveling the floor.
+	align_room: bool,
+}
+impl Args {
+	fn parse() -> Self {
+		let mut args = Args::default();
+		let mut iter = std::env::args_os().skip(1);
+		while let Some(arg) = iter.next() {
+			match arg.to_str() {
+				Some("--record") => args.record = iter.next().map(PathBuf::from),
+				Some("--replay") => args.replay = iter.next().map(PathBuf::from),
+				Some("--replay-output") => args.replay_output = iter.next().map(PathBuf::from),
+				Some("--calibrate") => args.calibrate = true,
+				Some("--level-floor") => args.level_floor = true,
+				Some("--align-room") => args.align_room = true,
+				_ => warn!("unknown argument {arg:?}"),
+			}
+		}
+		args
+	}
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+	// the movement task holds backends that can't leave this thread
+	tokio::task::LocalSet::new().run_until(run()).await;
+}
+
+async fn run() {
 	tracing_subscriber::fmt().pretty().with_file(false).init();
+	let args = Args::parse();
+	if let Some(replay) = &args.replay {
+		if let Err(err) = recording::replay_file(replay, args.replay_output.as_deref()).await {
+			error!("unable to replay {}: {err}", replay.display());
+		}
+		return;
+	}
+
 	let client = Client::connect().await.unwrap();
 	client
 		.setup_resources(&[&project_local_resources!("data")])
@@ -38,13 +109,40 @@ async fn main() {
 	// let mut button_hand = ModeButton::new(&client, ButtonLocation::Hand).await;
 	// let mut button_controller = ModeButton::new(&client, ButtonLocation::Controller).await;
 
-	let input = Input::new_pen(&client, conn.clone()).await.unwrap();
+	let settings = Settings::load();
+	let input = Input::new_pen(&client, conn.clone(), &settings)
+		.await
+		.unwrap();
 
-	let mut solar_sailer = SolarSailer::new(client.clone(), object_registry, input).await;
+	let mut solar_sailer =
+		SolarSailer::new(client.clone(), object_registry, input, &settings).await;
+	if let Some(record) = &args.record {
+		solar_sailer.record_to(record);
+	}
+	if args.calibrate {
+		solar_sailer.start_calibration(settings.profile.clone());
+	}
+	if args.level_floor {
+		solar_sailer.start_floor_leveling(&client, &settings).await;
+	}
+	if args.align_room {
+		solar_sailer.start_wall_alignment(&client).await;
+	}
 
+	let interrupted = tokio::spawn(tokio::signal::ctrl_c());
+	let terminated = match signal(SignalKind::terminate()) {
+		Ok(mut terminate) => Some(tokio::spawn(async move { terminate.recv().await })),
+		Err(err) => {
+			error!("unable to listen for SIGTERM: {err}");
+			None
+		}
+	};
 	let event_handle = async_loop.get_event_handle();
 	loop {
 		event_handle.wait().await;
+		if interrupted.is_finished() || terminated.as_ref().is_some_and(|t| t.is_finished()) {
+			break;
+		}
 		let Some(event) = client.get_root().recv_root_event() else {
 			continue;
 		};
@@ -52,27 +150,25 @@ async fn main() {
 			RootEvent::Ping { response } => response.send_ok(()),
 			RootEvent::Frame { info } => {
 				solar_sailer.handle_input();
+				if solar_sailer.should_quit() {
+					break;
+				}
+				solar_sailer.update_pen().await;
+				solar_sailer.update_floor_leveling(info.delta).await;
+				solar_sailer.update_wall_alignment().await;
+				solar_sailer.update_pick().await;
+				solar_sailer.update_ride().await;
 				let switch_mode = solar_sailer.should_switch_mode();
-				// if switch_mode {
-				// 	solar_sailer.mode = match solar_sailer.mode {
-				// 		Mode::Disabled => Mode::MonadoOffset,
-				// 		Mode::MonadoOffset => Mode::Zone,
-				// 		Mode::Zone => Mode::Disabled,
-				// 	};
-				// }
 				if switch_mode {
-					solar_sailer.switch_mode(match solar_sailer.current_mode() {
-						Mode::Reparent => Mode::MonadoOffset,
-						Mode::MonadoOffset => Mode::Reparent,
-						Mode::Disabled => Mode::MonadoOffset,
-					});
+					solar_sailer.switch_mode(solar_sailer.next_mode());
 				}
 
-				solar_sailer.update_signifiers();
+				solar_sailer.update_signifiers().await;
 				solar_sailer.update_velocity(info.delta).await;
-				solar_sailer.apply_offset(info.delta).await;
+				solar_sailer.apply_offset(info.delta);
 			}
 			RootEvent::SaveState { response: _ } => {}
 		}
 	}
+	solar_sailer.shutdown().await;
 }
@@ -1,68 +1,470 @@
-use std::sync::Arc;
+use std::{sync::Arc, thread};
 
-use glam::Vec3;
+use glam::{Affine3A, Vec3};
 use libmonado::{Monado, Pose};
-use stardust_xr_fusion::{
-	ClientHandle,
-	objects::play_space,
-	spatial::{SpatialRef, SpatialRefAspect},
-};
+use stardust_xr_fusion::{ClientHandle, objects::play_space};
+use tokio::sync::{mpsc, oneshot};
 use tracing::error;
 
-use crate::solar_sailer::mat_from_transform;
+use crate::{
+	backend::{BackendError, BackendResult, OriginPose, TrackingOrigin, TrackingOriginStore},
+	bounds::{Bounds, Edge},
+	locomotion::Gravity,
+	orbit::Orbit,
+	pipelined_transform::PipelinedTransform,
+};
 
-pub struct MonadoMovement {
-	monado: Monado,
-	stage: SpatialRef,
+pub struct MonadoMovement<O> {
+	origins: O,
+	gravity: Gravity,
+	bounds: Option<Bounds>,
+	edge: Option<Edge>,
+	/// How far the origins are raised for seated use, and the floor along with them.
+	lift: f32,
+	/// Where the lift is measured from, since Monado keeps offsets between runs.
+	baseline: Option<Vec<TrackingOrigin>>,
+	/// How far floor leveling raised the origins, which walking keeps them at.
+	floor: f32,
 }
 
-impl MonadoMovement {
-	pub async fn apply_offset(
-		&mut self,
-		delta_secs: f32,
-		velocity_ref: &SpatialRef,
-		velocity: Vec3,
-	) {
-		let Ok(origins) = self
-			.monado
-			.tracking_origins()
-			.inspect_err(|err| error!("unable to get monado origins: {err}"))
-		else {
+impl<O: TrackingOriginStore> MonadoMovement<O> {
+	pub fn new(origins: O) -> Self {
+		MonadoMovement {
+			origins,
+			gravity: Gravity::default(),
+			bounds: None,
+			edge: None,
+			lift: 0.0,
+			baseline: None,
+			floor: 0.0,
+		}
+	}
+
+	/// Raises every origin `lift` meters above its baseline in stage space. Meant for
+	/// startup, since it puts the origins back on the baseline.
+	pub async fn set_lift(&mut self, lift: f32) {
+		let Some(baseline) = self.baseline().await else {
 			return;
 		};
+		match self.origins.set_offsets(&raised(&baseline, lift)).await {
+			Ok(()) => self.lift = lift,
+			Err(err) => error!("unable to lift monado origins: {err}"),
+		}
+	}
 
-		let Ok(transform) = velocity_ref
-			.get_transform(&self.stage)
-			.await
-			.inspect_err(|err| error!("unable to get velocity_ref to stage transform: {err}"))
-		else {
+	/// Lowers the origins by the lift, wherever they've been moved since, so it isn't
+	/// applied twice next time.
+	pub async fn remove_lift(&mut self) {
+		let Some(origins) = self.placement().await else {
 			return;
 		};
-		let mat = mat_from_transform(&transform);
-		let delta_position = mat.transform_vector3(-velocity * delta_secs);
+		match self
+			.origins
+			.set_offsets(&raised(&origins, -self.lift))
+			.await
+		{
+			Ok(()) => self.lift = 0.0,
+			Err(err) => error!("unable to lower monado origins: {err}"),
+		}
+	}
 
-		for origin in origins {
-			let Some(Pose {
-				position,
-				orientation,
-			}) = origin.get_offset().ok()
-			else {
-				continue;
+	/// Offsets without the lift, as first seen or as restored from the room alignment.
+	async fn baseline(&mut self) -> Option<Vec<TrackingOrigin>> {
+		if self.baseline.is_none() {
+			self.baseline = Some(raised(&self.placement().await?, -self.lift));
+		}
+		self.baseline.clone()
+	}
+
+	/// Returns the offset applied to every origin, in stage space.
+	pub async fn apply_offset(&mut self, delta_secs: f32, velocity: Vec3) -> Vec3 {
+		let (bounds, edge) = (self.bounds.as_ref(), &mut self.edge);
+		move_origins(&mut self.origins, |mat, origins| {
+			let Some(origin) = origins.first() else {
+				return Affine3A::IDENTITY;
 			};
-			let offset_position = Vec3::from(position) + (delta_position);
+			let delta_position;
+			(delta_position, *edge) = Bounds::constrain_in(
+				bounds,
+				mat,
+				origin.offset.position,
+				mat.transform_vector3(-velocity * delta_secs),
+				delta_secs,
+			);
+			Affine3A::from_translation(delta_position)
+		})
+		.await
+	}
 
-			let _ = origin.set_offset(Pose {
-				position: offset_position.into(),
-				orientation,
-			});
+	/// Like `apply_offset` but sideways wafting turns the origins around the pivot.
+	pub async fn orbit(&mut self, delta_secs: f32, velocity: Vec3, orbit: Orbit) -> Vec3 {
+		let (bounds, edge) = (self.bounds.as_ref(), &mut self.edge);
+		move_origins(&mut self.origins, |mat, origins| {
+			let Some(origin) = origins.first() else {
+				return Affine3A::IDENTITY;
+			};
+			let motion;
+			(motion, *edge) = constrain_motion(
+				bounds,
+				mat,
+				origin.offset.position,
+				orbit.motion(mat, velocity, delta_secs),
+				delta_secs,
+			);
+			motion
+		})
+		.await
+	}
+
+	/// Carries the origins along with `motion` in the velocity reference space, like
+	/// standing on something that moved.
+	pub async fn follow(&mut self, delta_secs: f32, motion: Affine3A) -> Vec3 {
+		let (bounds, edge) = (self.bounds.as_ref(), &mut self.edge);
+		move_origins(&mut self.origins, |mat, origins| {
+			let Some(origin) = origins.first() else {
+				return Affine3A::IDENTITY;
+			};
+			let constrained;
+			(constrained, *edge) = constrain_motion(
+				bounds,
+				mat,
+				origin.offset.position,
+				mat * motion * mat.inverse(),
+				delta_secs,
+			);
+			constrained
+		})
+		.await
+	}
+
+	/// Like `apply_offset` but horizontal only, with gravity pulling the tracking floor
+	/// down to the leveled stage floor. Upward thrust while grounded jumps.
+	pub async fn walk(&mut self, delta_secs: f32, velocity: Vec3, thrust: Vec3) -> Vec3 {
+		if self.gravity.is_grounded() && velocity == Vec3::ZERO && thrust == Vec3::ZERO {
+			return Vec3::ZERO;
+		}
+		let (gravity, bounds, edge, ground) = (
+			&mut self.gravity,
+			self.bounds.as_ref(),
+			&mut self.edge,
+			self.lift + self.floor,
+		);
+		move_origins(&mut self.origins, |mat, origins| {
+			let Some(origin) = origins.first() else {
+				return Affine3A::IDENTITY;
+			};
+			let mut delta_position;
+			(delta_position, *edge) = Bounds::constrain_in(
+				bounds,
+				mat,
+				origin.offset.position,
+				mat.transform_vector3(-velocity * delta_secs).with_y(0.0),
+				delta_secs,
+			);
+			let upward_thrust = mat.transform_vector3(-thrust).y;
+			delta_position.y =
+				gravity.step(delta_secs, origin.offset.position.y - ground, upward_thrust);
+			Affine3A::from_translation(delta_position)
+		})
+		.await
+	}
+
+	/// Every origin's current offset.
+	pub async fn placement(&mut self) -> Option<Vec<TrackingOrigin>> {
+		self.origins
+			.origins()
+			.await
+			.inspect_err(|err| error!("unable to get monado origins: {err}"))
+			.ok()
+	}
+	pub async fn place(&mut self, origins: &[TrackingOrigin]) {
+		if let Err(err) = self.origins.set_offsets(origins).await {
+			error!("unable to set monado origin offsets: {err}");
+		}
+	}
+
+	/// Moves the origins named in `selected`, or every one if it's empty, by the stage
+	/// space `correction`. Returns every origin before and after.
+	pub async fn correct(
+		&mut self,
+		correction: Affine3A,
+		selected: &[String],
+	) -> Option<(Vec<TrackingOrigin>, Vec<TrackingOrigin>)> {
+		let before = self.placement().await?;
+		let (_, rotation, _) = correction.to_scale_rotation_translation();
+		let after = before
+			.iter()
+			.map(
+				|origin| match selected.is_empty() || selected.contains(&origin.name) {
+					true => TrackingOrigin {
+						offset: OriginPose {
+							position: correction.transform_point3(origin.offset.position),
+							orientation: rotation * origin.offset.orientation,
+						},
+						..origin.clone()
+					},
+					false => origin.clone(),
+				},
+			)
+			.collect::<Vec<_>>();
+		self.origins
+			.set_offsets(&after)
+			.await
+			.inspect_err(|err| error!("unable to correct monado origins: {err}"))
+			.ok()?;
+		Some((before, after))
+	}
+
+	/// Makes each named origin's offset its baseline, and puts it there plus the lift.
+	/// `floor` is how far floor leveling had raised them.
+	pub async fn restore(&mut self, offsets: &[(String, OriginPose)], floor: f32) {
+		let Some(mut baseline) = self.baseline().await else {
+			return;
+		};
+		for origin in &mut baseline {
+			if let Some((_, offset)) = offsets.iter().find(|(name, _)| *name == origin.name) {
+				origin.offset = *offset;
+			}
 		}
+		self.place(&raised(&baseline, self.lift)).await;
+		self.baseline = Some(baseline);
+		self.floor = floor;
+	}
+
+	pub fn lift(&self) -> f32 {
+		self.lift
+	}
+
+	pub fn floor(&self) -> f32 {
+		self.floor
+	}
+	/// Walk `by` meters higher up in stage space, after floor leveling raised the origins.
+	pub fn raise_floor(&mut self, by: f32) {
+		self.floor += by;
 	}
 
-	pub async fn from_monado(client: &Arc<ClientHandle>, monado: Option<Monado>) -> Option<Self> {
-		let monado = monado?;
-		Some(MonadoMovement {
-			monado,
-			stage: play_space(client).await?.spatial,
+	pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+		self.bounds = bounds;
+		self.edge = None;
+	}
+
+	/// Closest edge of the bounds as of the last movement, normal in velocity space.
+	pub fn edge(&self) -> Option<Edge> {
+		self.edge
+	}
+
+	/// Start falling toward the floor, e.g. after flying.
+	pub fn start_walking(&mut self) {
+		self.gravity = Gravity::default();
+	}
+
+	#[cfg(test)]
+	pub fn origins(&self) -> &O {
+		&self.origins
+	}
+}
+
+/// `origins` moved `by` meters up in stage space.
+fn raised(origins: &[TrackingOrigin], by: f32) -> Vec<TrackingOrigin> {
+	origins
+		.iter()
+		.map(|origin| TrackingOrigin {
+			offset: OriginPose {
+				position: origin.offset.position + Vec3::Y * by,
+				..origin.offset
+			},
+			..origin.clone()
+		})
+		.collect()
+}
+
+/// Shifts stage space `motion` so it doesn't take `position` out of the bounds.
+fn constrain_motion(
+	bounds: Option<&Bounds>,
+	velocity_to_stage: Affine3A,
+	position: Vec3,
+	motion: Affine3A,
+	delta_secs: f32,
+) -> (Affine3A, Option<Edge>) {
+	let moved = motion.transform_point3(position) - position;
+	let (allowed, edge) =
+		Bounds::constrain_in(bounds, velocity_to_stage, position, moved, delta_secs);
+	(Affine3A::from_translation(allowed - moved) * motion, edge)
+}
+
+/// Moves every origin by the stage space motion `motion` returns for the velocity_ref to
+/// stage transform. Returns how far the first origin moved.
+async fn move_origins<O: TrackingOriginStore>(
+	store: &mut O,
+	motion: impl FnOnce(Affine3A, &[TrackingOrigin]) -> Affine3A,
+) -> Vec3 {
+	let Ok(origins) = store
+		.origins()
+		.await
+		.inspect_err(|err| error!("unable to get monado origins: {err}"))
+	else {
+		return Vec3::ZERO;
+	};
+
+	let Ok(mat) = store
+		.velocity_to_stage()
+		.await
+		.inspect_err(|err| error!("unable to get velocity_ref to stage transform: {err}"))
+	else {
+		return Vec3::ZERO;
+	};
+	let motion = motion(mat, &origins);
+	let delta_position = origins
+		.first()
+		.map(|origin| motion.transform_point3(origin.offset.position) - origin.offset.position)
+		.unwrap_or_default();
+	let (_, rotation, _) = motion.to_scale_rotation_translation();
+
+	let origins = origins
+		.into_iter()
+		.map(|origin| TrackingOrigin {
+			offset: OriginPose {
+				position: motion.transform_point3(origin.offset.position),
+				orientation: rotation * origin.offset.orientation,
+			},
+			..origin
+		})
+		.collect::<Vec<_>>();
+	if let Err(err) = store.set_offsets(&origins).await {
+		error!("unable to set monado origin offsets: {err}");
+	}
+	delta_position
+}
+
+/// A call for the Monado thread to make, answered on the sender.
+enum Request {
+	Origins(oneshot::Sender<BackendResult<Vec<TrackingOrigin>>>),
+	SetOffsets(Vec<TrackingOrigin>, oneshot::Sender<BackendResult<()>>),
+}
+
+/// Tracking origins of a live Monado instance, moved relative to the play space.
+///
+/// libmonado blocks on IPC, so it's only ever called from a thread of its own.
+pub struct MonadoOrigins {
+	requests: mpsc::UnboundedSender<Request>,
+	velocity_to_stage: PipelinedTransform,
+}
+
+impl MonadoOrigins {
+	/// Nothing if Monado or the play space isn't there.
+	pub async fn connect(client: &Arc<ClientHandle>) -> Option<Self> {
+		let (connected, connection) = oneshot::channel();
+		let (requests, requests_rx) = mpsc::unbounded_channel();
+		if let Err(err) = thread::Builder::new()
+			.name("monado".to_string())
+			.spawn(move || Self::serve(connected, requests_rx))
+		{
+			error!("unable to start monado thread: {err}");
+			return None;
+		}
+		match connection.await {
+			Ok(Ok(())) => {}
+			Ok(Err(err)) => {
+				error!("Couldn't connect to monado :( {err}");
+				return None;
+			}
+			Err(_) => return None,
+		}
+		Some(MonadoOrigins {
+			requests,
+			velocity_to_stage: PipelinedTransform::new(
+				client.get_root().clone().as_spatial_ref(),
+				play_space(client).await?.spatial,
+			),
 		})
 	}
+
+	/// Runs on the Monado thread until the `MonadoOrigins` is dropped.
+	fn serve(
+		connected: oneshot::Sender<BackendResult<()>>,
+		mut requests: mpsc::UnboundedReceiver<Request>,
+	) {
+		let monado = match Monado::auto_connect() {
+			Ok(monado) => monado,
+			Err(err) => {
+				_ = connected.send(Err(BackendError::new(err)));
+				return;
+			}
+		};
+		_ = connected.send(Ok(()));
+		while let Some(request) = requests.blocking_recv() {
+			match request {
+				Request::Origins(reply) => _ = reply.send(read_origins(&monado)),
+				Request::SetOffsets(offsets, reply) => {
+					_ = reply.send(write_offsets(&monado, &offsets));
+				}
+			}
+		}
+	}
+
+	async fn request<T>(
+		&self,
+		request: impl FnOnce(oneshot::Sender<BackendResult<T>>) -> Request,
+	) -> BackendResult<T> {
+		let (reply, response) = oneshot::channel();
+		let stopped = || BackendError::new("monado thread stopped");
+		self.requests.send(request(reply)).map_err(|_| stopped())?;
+		response.await.map_err(|_| stopped())?
+	}
+}
+
+impl TrackingOriginStore for MonadoOrigins {
+	async fn velocity_to_stage(&mut self) -> BackendResult<Affine3A> {
+		self.velocity_to_stage.get().await
+	}
+
+	async fn origins(&mut self) -> BackendResult<Vec<TrackingOrigin>> {
+		self.request(Request::Origins).await
+	}
+
+	async fn set_offsets(&mut self, offsets: &[TrackingOrigin]) -> BackendResult<()> {
+		self.request(|reply| Request::SetOffsets(offsets.to_vec(), reply))
+			.await
+	}
+}
+
+fn read_origins(monado: &Monado) -> BackendResult<Vec<TrackingOrigin>> {
+	let origins = monado.tracking_origins().map_err(BackendError::new)?;
+	Ok(origins
+		.into_iter()
+		.filter_map(|origin| {
+			let Pose {
+				position,
+				orientation,
+			} = origin.get_offset().ok()?;
+			Some(TrackingOrigin {
+				id: origin.id,
+				name: origin.name.clone(),
+				offset: OriginPose {
+					position: position.into(),
+					orientation: orientation.into(),
+				},
+			})
+		})
+		.collect())
+}
+
+fn write_offsets(monado: &Monado, offsets: &[TrackingOrigin]) -> BackendResult<()> {
+	let origins = monado.tracking_origins().map_err(BackendError::new)?;
+	let mut result = Ok(());
+	for origin in origins {
+		let Some(new) = offsets.iter().find(|new| new.id == origin.id) else {
+			continue;
+		};
+		if let Err(err) = origin.set_offset(Pose {
+			position: new.offset.position.into(),
+			orientation: new.offset.orientation.into(),
+		}) {
+			result = Err(BackendError(format!(
+				"unable to set offset of {}: {err}",
+				new.name
+			)));
+		}
+	}
+	result
 }
@@ -0,0 +1,373 @@
+use std::{
+	cell::RefCell,
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+use glam::{Affine3A, Vec3};
+use tokio::{
+	sync::{mpsc, oneshot, watch},
+	task::JoinHandle,
+};
+use tracing::{error, info};
+
+use crate::{
+	backend::{BackendResult, SpatialTree, TrackingOrigin, TrackingOriginStore},
+	bounds::Edge,
+	history::HistoryAction,
+	locomotion::{Mode, MotionTarget, Movement},
+	room::RoomAlignment,
+};
+
+/// How long individual backend calls have taken since the last report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallTimings {
+	pub calls: u32,
+	pub total: Duration,
+	pub max: Duration,
+}
+impl CallTimings {
+	fn record(&mut self, took: Duration) {
+		self.calls += 1;
+		self.total += took;
+		self.max = self.max.max(took);
+	}
+	pub fn mean(&self) -> Duration {
+		self.total.checked_div(self.calls).unwrap_or_default()
+	}
+}
+
+/// Shared between every `Timed` backend and the movement task that reports on them.
+#[derive(Debug, Clone, Default)]
+pub struct CallTimer(Rc<RefCell<CallTimings>>);
+impl CallTimer {
+	fn time<T>(&self, started: Instant, result: T) -> T {
+		self.0.borrow_mut().record(started.elapsed());
+		result
+	}
+	#[cfg(test)]
+	fn timings(&self) -> CallTimings {
+		*self.0.borrow()
+	}
+	fn take(&self) -> CallTimings {
+		self.0.take()
+	}
+}
+
+/// A backend whose every call is timed.
+pub struct Timed<B> {
+	backend: B,
+	timer: CallTimer,
+}
+impl<B> Timed<B> {
+	pub fn new(backend: B, timer: &CallTimer) -> Self {
+		Timed {
+			backend,
+			timer: timer.clone(),
+		}
+	}
+}
+
+impl<B: SpatialTree> SpatialTree for Timed<B> {
+	async fn velocity_to_world(&mut self) -> BackendResult<Affine3A> {
+		let started = Instant::now();
+		let result = self.backend.velocity_to_world().await;
+		self.timer.time(started, result)
+	}
+	fn set_world_translation(&mut self, translation: Vec3) -> BackendResult<()> {
+		let started = Instant::now();
+		let result = self.backend.set_world_translation(translation);
+		self.timer.time(started, result)
+	}
+	fn begin_reparenting(&mut self) {
+		self.backend.begin_reparenting();
+	}
+	fn end_reparenting(&mut self) {
+		self.backend.end_reparenting();
+	}
+}
+
+impl<B: TrackingOriginStore> TrackingOriginStore for Timed<B> {
+	async fn velocity_to_stage(&mut self) -> BackendResult<Affine3A> {
+		let started = Instant::now();
+		let result = self.backend.velocity_to_stage().await;
+		self.timer.time(started, result)
+	}
+	async fn origins(&mut self) -> BackendResult<Vec<TrackingOrigin>> {
+		let started = Instant::now();
+		let result = self.backend.origins().await;
+		self.timer.time(started, result)
+	}
+	async fn set_offsets(&mut self, offsets: &[TrackingOrigin]) -> BackendResult<()> {
+		let started = Instant::now();
+		let result = self.backend.set_offsets(offsets).await;
+		self.timer.time(started, result)
+	}
+}
+
+/// Carried out by the backend before its next step.
+#[derive(Debug)]
+enum Command {
+	History(HistoryAction),
+	PlaceZone(Vec3),
+	LevelFloor {
+		correction: f32,
+		selected: Vec<String>,
+	},
+	/// Corrects the origins, saving where they end up to reapply next time.
+	AlignRoom {
+		correction: Affine3A,
+		selected: Vec<String>,
+	},
+	/// Takes the lift off and stops, then answers.
+	Shutdown(oneshot::Sender<()>),
+}
+
+/// What the movement backend did on its latest step.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementStatus {
+	pub edge: Option<Edge>,
+}
+
+/// Offset the backend applied on one step, covering every target up to `target`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+	/// As returned by `set_target`.
+	pub target: u64,
+	pub offset: Vec3,
+}
+
+/// Runs `Movement` on its own task so slow Monado or server calls don't hold up frames.
+///
+/// Targets sent faster than the backend keeps up with are coalesced into the latest one,
+/// covering all of their frame time.
+pub struct MovementTask {
+	targets: mpsc::UnboundedSender<(u64, MotionTarget)>,
+	/// Number the next target is sent with.
+	next_target: u64,
+	commands: mpsc::UnboundedSender<Command>,
+	status: watch::Receiver<MovementStatus>,
+	steps: mpsc::UnboundedReceiver<Step>,
+	task: JoinHandle<()>,
+}
+
+impl MovementTask {
+	const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+	/// Must be called inside a `LocalSet`, since the backends aren't `Send`. Reports the
+	/// calls timed by `timer`.
+	pub fn spawn<S, O>(movement: Movement<S, O>, timer: CallTimer) -> Self
+	where
+		S: SpatialTree + 'static,
+		O: TrackingOriginStore + 'static,
+	{
+		let (targets, targets_rx) = mpsc::unbounded_channel();
+		let (status_tx, status) = watch::channel(MovementStatus {
+			edge: movement.edge(),
+		});
+		let (steps_tx, steps) = mpsc::unbounded_channel();
+		let (commands, commands_rx) = mpsc::unbounded_channel();
+		let task = tokio::task::spawn_local(Self::run(
+			movement,
+			targets_rx,
+			commands_rx,
+			status_tx,
+			steps_tx,
+			timer,
+		));
+		MovementTask {
+			targets,
+			next_target: 0,
+			commands,
+			status,
+			steps,
+			task,
+		}
+	}
+
+	async fn run<S: SpatialTree, O: TrackingOriginStore>(
+		mut movement: Movement<S, O>,
+		mut targets: mpsc::UnboundedReceiver<(u64, MotionTarget)>,
+		mut commands: mpsc::UnboundedReceiver<Command>,
+		status: watch::Sender<MovementStatus>,
+		steps: mpsc::UnboundedSender<Step>,
+		timer: CallTimer,
+	) {
+		let mut last_report = Instant::now();
+		while let Some((mut number, mut target)) = targets.recv().await {
+			while let Ok((later_number, later)) = targets.try_recv() {
+				number = later_number;
+				target = target.then(later);
+			}
+			if movement.current_mode() != target.mode {
+				movement.switch_mode(target.mode);
+			}
+			while let Ok(command) = commands.try_recv() {
+				match command {
+					Command::History(action) => movement.history(action).await,
+					Command::PlaceZone(center) => movement.place_zone(center),
+					Command::LevelFloor {
+						correction,
+						selected,
+					} => _ = movement.level_floor(correction, &selected).await,
+					Command::AlignRoom {
+						correction,
+						selected,
+					} => {
+						let Some(origins) = movement.correct_origins(correction, &selected).await
+						else {
+							continue;
+						};
+						let room = RoomAlignment::new(
+							&origins,
+							&selected,
+							movement.lift(),
+							movement.floor(),
+						);
+						if let Err(err) = room.save() {
+							error!("unable to save room alignment: {err}");
+						}
+					}
+					Command::Shutdown(done) => {
+						movement.remove_lift().await;
+						_ = done.send(());
+						return;
+					}
+				}
+			}
+			let offset = movement.apply_offset(target).await;
+			_ = steps.send(Step {
+				target: number,
+				offset,
+			});
+			status.send_replace(MovementStatus {
+				edge: movement.edge(),
+			});
+			if last_report.elapsed() >= Self::REPORT_INTERVAL {
+				let timings = timer.take();
+				info!(
+					calls = timings.calls,
+					mean = ?timings.mean(),
+					max = ?timings.max,
+					"movement backend timings"
+				);
+				last_report = Instant::now();
+			}
+		}
+	}
+
+	/// Sends the frame's target, waking the backend even if it didn't change. Returns the
+	/// number its `Step` will carry.
+	pub fn set_target(&mut self, target: MotionTarget) -> u64 {
+		let number = self.next_target;
+		self.next_target += 1;
+		_ = self.targets.send((number, target));
+		number
+	}
+
+	/// Carried out on the next step.
+	pub fn history(&self, action: HistoryAction) {
+		_ = self.commands.send(Command::History(action));
+	}
+
+	/// Centers the zone on `center` in the velocity reference space, on the next step.
+	pub fn place_zone(&self, center: Vec3) {
+		_ = self.commands.send(Command::PlaceZone(center));
+	}
+
+	/// Raises the Monado origins named in `selected`, or every one if it's empty, by
+	/// `correction` on the next step, undoable like any motion. Walking keeps to the
+	/// leveled floor.
+	pub fn level_floor(&self, correction: f32, selected: Vec<String>) {
+		_ = self.commands.send(Command::LevelFloor {
+			correction,
+			selected,
+		});
+	}
+
+	/// Moves the Monado origins named in `selected`, or every one if it's empty, by the stage
+	/// space `correction` on the next step, then saves the result to reapply on startup.
+	pub fn align_room(&self, correction: Affine3A, selected: Vec<String>) {
+		_ = self.commands.send(Command::AlignRoom {
+			correction,
+			selected,
+		});
+	}
+
+	/// Takes the seated lift back off the origins and stops the backend, still in `mode`.
+	pub async fn shutdown(&mut self, mode: Mode) {
+		let (done, finished) = oneshot::channel();
+		_ = self.commands.send(Command::Shutdown(done));
+		// commands only run when there's a new target
+		self.set_target(MotionTarget::still(mode));
+		_ = finished.await;
+	}
+
+	/// Latest status, which may lag the last target by a step.
+	pub fn status(&self) -> MovementStatus {
+		*self.status.borrow()
+	}
+
+	/// Steps the backend finished since the last call, oldest first.
+	pub fn finished_steps(&mut self) -> Vec<Step> {
+		let mut steps = Vec::new();
+		while let Ok(step) = self.steps.try_recv() {
+			steps.push(step);
+		}
+		steps
+	}
+}
+
+impl Drop for MovementTask {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+	use tokio::task::{LocalSet, yield_now};
+
+	use super::{CallTimer, MovementTask, Timed};
+	use crate::{
+		fake_backend::{FakeOrigins, FakeSpatialTree},
+		locomotion::{Mode, MotionTarget, Movement},
+	};
+
+	#[test]
+	fn backend_covers_every_coalesced_frame() {
+		let runtime = tokio::runtime::Builder::new_current_thread()
+			.build()
+			.unwrap();
+		LocalSet::new().block_on(&runtime, async {
+			let timer = CallTimer::default();
+			let movement = Movement::new(
+				Timed::new(FakeSpatialTree::new(), &timer),
+				Some(Timed::new(FakeOrigins::new(&["stage"]), &timer)),
+			);
+			let mut task = MovementTask::spawn(movement, timer.clone());
+			// sent before the backend gets a chance to step
+			for _ in 0..3 {
+				task.set_target(MotionTarget {
+					velocity: Vec3::X,
+					delta_secs: 0.5,
+					..MotionTarget::still(Mode::MonadoOffset)
+				});
+			}
+			yield_now().await;
+			let steps = task.finished_steps();
+			assert_eq!(steps.len(), 1, "{steps:?}");
+			assert_eq!(steps[0].target, 2);
+			assert!(
+				steps[0].offset.abs_diff_eq(Vec3::NEG_X * 1.5, 1e-5),
+				"{:?}",
+				steps[0].offset
+			);
+			// reading and setting the origins, plus the velocity_ref to stage transform
+			let timings = timer.timings();
+			assert!(timings.calls >= 3, "{timings:?}");
+			assert!(timings.max >= timings.mean());
+		});
+	}
+}
@@ -0,0 +1,79 @@
+use glam::{Affine3A, Vec3};
+
+/// Turning around a pivot in orbit mode, in the velocity reference space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+	pub pivot: Vec3,
+	/// Where the hand last was, which wafting turns around the pivot from.
+	pub hand: Vec3,
+}
+impl Orbit {
+	/// Closer to the pivot than this, wafting only pulls in and out.
+	const MIN_RADIUS: f32 = 0.05;
+
+	/// Splits hand `velocity` into a yaw rate around the pivot in rad/s, and what's left
+	/// toward or away from the pivot and vertically.
+	pub fn split(&self, velocity: Vec3) -> (f32, Vec3) {
+		let radius = (self.hand - self.pivot).with_y(0.0);
+		let distance = radius.length();
+		if distance < Self::MIN_RADIUS {
+			return (0.0, velocity);
+		}
+		let outward = radius / distance;
+		let yaw_rate = radius.cross(velocity).y / (distance * distance);
+		(
+			yaw_rate,
+			outward * velocity.dot(outward) + Vec3::Y * velocity.y,
+		)
+	}
+
+	/// How the user moves in stage space over `delta_secs`, against the hand like in
+	/// the other modes, so the world turns along with it.
+	pub fn motion(&self, velocity_to_stage: Affine3A, velocity: Vec3, delta_secs: f32) -> Affine3A {
+		let (yaw_rate, linear) = self.split(velocity);
+		let pivot = velocity_to_stage.transform_point3(self.pivot);
+		let pull = velocity_to_stage.transform_vector3(-linear * delta_secs);
+		Affine3A::from_translation(pivot + pull)
+			* Affine3A::from_rotation_y(-yaw_rate * delta_secs)
+			* Affine3A::from_translation(-pivot)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::f32::consts::FRAC_PI_2;
+
+	use glam::{Affine3A, Vec3};
+
+	use super::Orbit;
+
+	#[test]
+	fn sideways_waft_turns_around_pivot() {
+		let orbit = Orbit {
+			pivot: Vec3::ZERO,
+			hand: Vec3::X,
+		};
+		assert_eq!(orbit.split(Vec3::NEG_Z), (1.0, Vec3::ZERO));
+		assert_eq!(orbit.split(Vec3::X), (0.0, Vec3::X));
+
+		let user = Vec3::X * 2.0;
+		let turned = orbit
+			.motion(Affine3A::IDENTITY, Vec3::NEG_Z, FRAC_PI_2)
+			.transform_point3(user);
+		assert!((turned.length() - 2.0).abs() < 1e-5);
+		assert!(turned.distance(Vec3::Z * 2.0) < 1e-5, "{turned}");
+	}
+
+	#[test]
+	fn pulling_the_hand_back_draws_the_user_in() {
+		let orbit = Orbit {
+			pivot: Vec3::ZERO,
+			hand: Vec3::X,
+		};
+		let user = Vec3::X * 1.5;
+		let pulled = orbit
+			.motion(Affine3A::from_translation(Vec3::Y), Vec3::X, 0.5)
+			.transform_point3(user);
+		assert!((pulled - Vec3::X).length() < 1e-5, "{pulled}");
+	}
+}
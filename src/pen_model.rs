@@ -0,0 +1,129 @@
+use std::{
+	f32::consts::TAU,
+	time::{Duration, Instant},
+};
+
+use glam::{Quat, Vec3};
+use stardust_xr_fusion::{
+	drawable::{MaterialParameter, Model, ModelPart, ModelPartAspect as _},
+	node::NodeResult,
+	spatial::{SpatialAspect as _, SpatialRefAspect, Transform},
+	values::ResourceID,
+};
+
+use crate::{
+	APP_ID,
+	locomotion::Mode,
+	theme::{Theme, ThemeColor},
+};
+
+/// What the pen model shows on a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PenState {
+	pub mode: Mode,
+	pub held: bool,
+	pub thrusting: bool,
+	pub precision: bool,
+	/// Meters per second.
+	pub speed: f32,
+}
+
+/// `pen.glb`: a grip, a tip and a mode ring, tinted by the pen's state.
+pub struct PenModel {
+	_model: Model,
+	grip: ModelPart,
+	tip: ModelPart,
+	mode_ring: ModelPart,
+	theme: Theme,
+	/// Last shown, so an unchanged state sends nothing.
+	state: Option<PenState>,
+	mode_changed: Option<Instant>,
+}
+
+impl PenModel {
+	const MODE_ANIMATION: Duration = Duration::from_millis(400);
+	const RING_HEIGHT: f32 = 0.071;
+	/// Speed the tip glows brightest at.
+	const GLOW_SPEED: f32 = 10.0;
+
+	pub fn create(parent: &impl SpatialRefAspect, theme: &Theme) -> NodeResult<Self> {
+		let model = Model::create(
+			parent,
+			Transform::identity(),
+			&ResourceID::new_namespaced(APP_ID, "pen"),
+		)?;
+		Ok(PenModel {
+			grip: model.part("grip")?,
+			tip: model.part("tip")?,
+			mode_ring: model.part("mode_ring")?,
+			_model: model,
+			theme: theme.clone(),
+			state: None,
+			mode_changed: None,
+		})
+	}
+
+	pub fn update(&mut self, state: PenState) {
+		let last = self.state.replace(state);
+		if last.is_some_and(|last| last.mode != state.mode) {
+			self.mode_changed = Some(Instant::now());
+		}
+		if last != Some(state) {
+			self.show(state);
+		}
+		self.animate_mode_ring();
+	}
+
+	fn show(&self, state: PenState) {
+		let mode_color = self.theme.mode(state.mode).color.linear();
+		let grip_color = match state.thrusting {
+			true => self.theme.grabbing.color.linear(),
+			false => mode_color,
+		};
+		_ = self
+			.grip
+			.set_material_parameter("color", MaterialParameter::Color(grip_color));
+		// a thinner grip while the precision modifier is held
+		let grip_width = match state.precision {
+			true => 0.5,
+			false => 1.0,
+		};
+		_ = self
+			.grip
+			.set_local_transform(Transform::from_scale([grip_width, 1.0, grip_width]));
+
+		let glow = match state.thrusting || state.held {
+			true => (state.speed / Self::GLOW_SPEED).clamp(0.1, 1.0),
+			false => 0.0,
+		};
+		let [r, g, b, _] = self.theme.grabbing.color.0;
+		_ = self.tip.set_material_parameter(
+			"emission_factor",
+			MaterialParameter::Color(ThemeColor([r * glow, g * glow, b * glow, 1.0]).linear()),
+		);
+
+		_ = self
+			.mode_ring
+			.set_material_parameter("color", MaterialParameter::Color(mode_color));
+	}
+
+	fn animate_mode_ring(&mut self) {
+		let t = match self.mode_changed {
+			Some(changed) => changed.elapsed().as_secs_f32() / Self::MODE_ANIMATION.as_secs_f32(),
+			None => 1.0,
+		};
+		if t >= 1.0 && self.mode_changed.take().is_none() {
+			return;
+		}
+		// one spin with a pulse that settles back down
+		let t = t.min(1.0);
+		let eased = 1.0 - (1.0 - t).powi(3);
+		_ = self
+			.mode_ring
+			.set_local_transform(Transform::from_translation_rotation_scale(
+				[0.0, Self::RING_HEIGHT, 0.0],
+				Quat::from_rotation_y(eased * TAU),
+				Vec3::splat(1.0 + (1.0 - eased) * 0.5),
+			));
+	}
+}
@@ -0,0 +1,71 @@
+use glam::Affine3A;
+use stardust_xr_fusion::spatial::{SpatialRef, SpatialRefAspect as _};
+use tokio::task::JoinHandle;
+
+use crate::{
+	backend::{BackendError, BackendResult},
+	solar_sailer::mat_from_transform,
+};
+
+/// The transform of one spatial relative to another, refreshed in the background so
+/// reading it rarely waits on the server. Reads lag the server by a frame, or a few while
+/// the server is slow, so it's best for transforms that change smoothly or not at all.
+pub struct PipelinedTransform {
+	spatial: SpatialRef,
+	relative_to: SpatialRef,
+	latest: Option<Affine3A>,
+	/// Reads answered with `latest` since it arrived.
+	stale_reads: u32,
+	refresh: Option<JoinHandle<BackendResult<Affine3A>>>,
+}
+
+impl PipelinedTransform {
+	/// Past this many reads of the same value, reading waits on the refresh instead.
+	const MAX_STALE_READS: u32 = 2;
+
+	pub fn new(spatial: SpatialRef, relative_to: SpatialRef) -> Self {
+		PipelinedTransform {
+			spatial,
+			relative_to,
+			latest: None,
+			stale_reads: 0,
+			refresh: None,
+		}
+	}
+
+	pub async fn get(&mut self) -> BackendResult<Affine3A> {
+		let refresh = match self.refresh.take() {
+			Some(refresh) => refresh,
+			None => self.spawn_refresh(),
+		};
+		if let Some(latest) = self.latest
+			&& !refresh.is_finished()
+			&& self.stale_reads < Self::MAX_STALE_READS
+		{
+			self.stale_reads += 1;
+			self.refresh = Some(refresh);
+			return Ok(latest);
+		}
+		let result = refresh
+			.await
+			.map_err(BackendError::new)
+			.and_then(|result| result);
+		// start on the next read right away so it's ready by then
+		self.refresh = Some(self.spawn_refresh());
+		let latest = result?;
+		self.latest = Some(latest);
+		self.stale_reads = 0;
+		Ok(latest)
+	}
+
+	fn spawn_refresh(&self) -> JoinHandle<BackendResult<Affine3A>> {
+		let (spatial, relative_to) = (self.spatial.clone(), self.relative_to.clone());
+		tokio::spawn(async move {
+			spatial
+				.get_transform(&relative_to)
+				.await
+				.map(|transform| mat_from_transform(&transform))
+				.map_err(BackendError::new)
+		})
+	}
+}
@@ -0,0 +1,137 @@
+use std::{
+	fmt::Write as _,
+	time::{Duration, Instant},
+};
+
+use stardust_xr_fusion::{
+	drawable::{Text, TextAspect as _, TextStyle, XAlign, YAlign},
+	node::NodeResult,
+	spatial::{SpatialRefAspect, Transform},
+	values::color::rgba_linear,
+};
+
+use crate::{
+	bounds::Edge,
+	locomotion::{Gears, Mode},
+};
+
+/// Mode, speed and gear as text next to the pen's button, hidden when left alone.
+pub struct Readout {
+	text: Text,
+	shown: String,
+	/// Mode, gear and precision last frame, so changing them counts as interaction.
+	last_state: Option<(Mode, usize, bool)>,
+	last_interaction: Instant,
+}
+
+impl Readout {
+	const HIDE_AFTER: Duration = Duration::from_secs(3);
+
+	pub fn create(parent: &impl SpatialRefAspect, transform: Transform) -> NodeResult<Self> {
+		Ok(Readout {
+			text: Text::create(
+				parent,
+				transform,
+				"",
+				TextStyle {
+					character_height: 0.008,
+					color: rgba_linear!(1.0, 1.0, 1.0, 1.0),
+					text_align_x: XAlign::Left,
+					text_align_y: YAlign::Center,
+					..Default::default()
+				},
+			)?,
+			shown: String::new(),
+			last_state: None,
+			last_interaction: Instant::now(),
+		})
+	}
+
+	/// `held` counts as interaction, as does any change of mode or gear.
+	pub fn update(
+		&mut self,
+		held: bool,
+		mode: Mode,
+		gears: &Gears,
+		speed: f32,
+		edge: Option<Edge>,
+	) {
+		let state = (mode, gears.current(), gears.precision());
+		if held || self.last_state != Some(state) {
+			self.last_interaction = Instant::now();
+		}
+		self.last_state = Some(state);
+
+		let label = match self.last_interaction.elapsed() < Self::HIDE_AFTER {
+			true => Self::label(mode, gears, speed, edge),
+			false => String::new(),
+		};
+		if label != self.shown {
+			_ = self.text.set_text(&label);
+			self.shown = label;
+		}
+	}
+
+	fn label(mode: Mode, gears: &Gears, speed: f32, edge: Option<Edge>) -> String {
+		let mut label = format!(
+			"{mode}\n{speed:.1} m/s\ngear {} (x{})",
+			gears.current() + 1,
+			gears.multiplier()
+		);
+		if gears.precision() {
+			label.push_str(" precise");
+		}
+		match edge {
+			Some(edge) if edge.proximity >= 1.0 => _ = write!(label, "\nat bounds"),
+			Some(edge) if edge.proximity > 0.0 => _ = write!(label, "\nnear bounds"),
+			_ => {}
+		}
+		label
+	}
+}
+
+/// Instructions floating in front of `parent`, best the head.
+pub fn prompt(parent: &impl SpatialRefAspect, text: &str) -> NodeResult<Text> {
+	Text::create(
+		parent,
+		Transform::from_translation([0.0, -0.05, -0.5]),
+		text,
+		TextStyle {
+			character_height: 0.02,
+			color: rgba_linear!(1.0, 1.0, 1.0, 1.0),
+			text_align_x: XAlign::Center,
+			text_align_y: YAlign::Center,
+			..Default::default()
+		},
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+
+	use super::Readout;
+	use crate::{
+		bounds::Edge,
+		locomotion::{Gears, Mode},
+	};
+
+	#[test]
+	fn label_shows_mode_speed_gear_and_bounds() {
+		let mut gears = Gears::default();
+		assert_eq!(
+			Readout::label(Mode::Walk, &gears, 1.234, None),
+			"Walk\n1.2 m/s\ngear 2 (x1)"
+		);
+		gears.set_precision(true);
+		let edge = Edge {
+			normal: Vec3::X,
+			distance: -0.5,
+			proximity: 0.5,
+		};
+		assert_eq!(
+			Readout::label(Mode::MonadoOffset, &gears, 0.0, Some(edge)),
+			"Offset\n0.0 m/s\ngear 2 (x0.2) precise\nnear bounds"
+		);
+	}
+}
@@ -0,0 +1,332 @@
+use std::{
+	fs::File,
+	io::{self, BufRead, BufReader, LineWriter, Write},
+	path::Path,
+};
+
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+	backend::OriginPose,
+	bounds::Bounds,
+	fake_backend::{FakeOrigins, FakeSpatialTree},
+	locomotion::{Gears, Locomotion, Mode, Pointing, WaftSample},
+};
+
+/// Settings locomotion ran with, written as the first line of a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+	/// Thrust multiplier of every gear, indexed by `FrameRecord::gear`.
+	pub gears: Vec<f32>,
+	pub precision_multiplier: f32,
+	#[serde(default)]
+	pub bounds: Option<Bounds>,
+	#[serde(default)]
+	pub seated_lift: f32,
+	/// Offset the first Monado origin started at, without the lift.
+	#[serde(default)]
+	pub start_position: [f32; 3],
+	#[serde(default = "RecordingHeader::default_orientation")]
+	pub start_orientation: [f32; 4],
+}
+impl Default for RecordingHeader {
+	fn default() -> Self {
+		RecordingHeader::new(&Gears::default())
+	}
+}
+impl RecordingHeader {
+	pub fn new(gears: &Gears) -> Self {
+		RecordingHeader {
+			gears: gears.multipliers().to_vec(),
+			precision_multiplier: gears.precision_multiplier(),
+			bounds: None,
+			seated_lift: 0.0,
+			start_position: [0.0; 3],
+			start_orientation: Self::default_orientation(),
+		}
+	}
+	fn default_orientation() -> [f32; 4] {
+		Quat::IDENTITY.into()
+	}
+	pub fn set_start(&mut self, start: OriginPose) {
+		self.start_position = start.position.into();
+		self.start_orientation = start.orientation.into();
+	}
+	fn gears(&self) -> Gears {
+		Gears::new(self.gears.clone(), 0, self.precision_multiplier)
+	}
+	/// Fake origins where the recording started, before the lift.
+	fn origins(&self) -> FakeOrigins {
+		let mut origins = FakeOrigins::new(&["replay"]);
+		origins.origins[0].offset = OriginPose {
+			position: self.start_position.into(),
+			orientation: Quat::from_array(self.start_orientation).normalize(),
+		};
+		origins
+	}
+}
+
+/// Everything that went into and came out of locomotion on one frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+	pub delta_secs: f32,
+	pub mode: Mode,
+	/// Grabbing hand, tip or gaze steering head position, in the velocity reference space.
+	pub position: Option<[f32; 3]>,
+	pub thrusting: bool,
+	/// Direction the pen pointed in when steering by pointing, in the velocity reference space.
+	#[serde(default)]
+	pub heading: Option<[f32; 3]>,
+	#[serde(default)]
+	pub throttle: f32,
+	/// Index into `RecordingHeader::gears`.
+	#[serde(default = "FrameRecord::default_gear")]
+	pub gear: usize,
+	#[serde(default)]
+	pub precision: bool,
+	/// The brake gesture fired this frame.
+	#[serde(default)]
+	pub brake: bool,
+	/// Orbit pivot in the velocity reference space.
+	#[serde(default)]
+	pub pivot: Option<[f32; 3]>,
+	pub velocity: [f32; 3],
+	/// Offset applied by the backend step for this frame, none if the step took in later
+	/// frames too.
+	pub offset: [f32; 3],
+}
+impl FrameRecord {
+	pub fn new(
+		delta_secs: f32,
+		mode: Mode,
+		sample: WaftSample,
+		gears: &Gears,
+		velocity: Vec3,
+	) -> Self {
+		FrameRecord {
+			delta_secs,
+			mode,
+			position: sample.position.map(Into::into),
+			thrusting: sample.thrusting,
+			heading: sample.pointing.map(|pointing| pointing.direction.into()),
+			throttle: sample.pointing.map_or(0.0, |pointing| pointing.throttle),
+			gear: gears.current(),
+			precision: gears.precision(),
+			brake: false,
+			pivot: None,
+			velocity: velocity.into(),
+			offset: [0.0; 3],
+		}
+	}
+	fn default_gear() -> usize {
+		Gears::default().current()
+	}
+	pub fn sample(&self) -> WaftSample {
+		WaftSample {
+			position: self.position.map(Vec3::from),
+			thrusting: self.thrusting,
+			pointing: self.heading.map(|heading| Pointing {
+				direction: heading.into(),
+				throttle: self.throttle,
+			}),
+		}
+	}
+}
+
+/// Writes a JSON `RecordingHeader` line, then one JSON `FrameRecord` per line.
+pub struct Recorder {
+	writer: LineWriter<File>,
+}
+impl Recorder {
+	pub fn create(path: &Path, header: &RecordingHeader) -> io::Result<Self> {
+		let mut recorder = Recorder {
+			writer: LineWriter::new(File::create(path)?),
+		};
+		recorder.write_line(header)?;
+		Ok(recorder)
+	}
+	pub fn record(&mut self, frame: &FrameRecord) -> io::Result<()> {
+		self.write_line(frame)
+	}
+	fn write_line(&mut self, value: &impl Serialize) -> io::Result<()> {
+		serde_json::to_writer(&mut self.writer, value)?;
+		self.writer.write_all(b"\n")
+	}
+}
+
+pub fn read_recording(path: &Path) -> io::Result<(RecordingHeader, Vec<FrameRecord>)> {
+	let mut lines = BufReader::new(File::open(path)?)
+		.lines()
+		.filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()));
+	let header = match lines.next() {
+		Some(line) => serde_json::from_str(&line?).map_err(|err| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("recording doesn't start with a header: {err}"),
+			)
+		})?,
+		None => {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"empty recording",
+			));
+		}
+	};
+	let frames = lines
+		.map(|line| Ok(serde_json::from_str(&line?)?))
+		.collect::<io::Result<_>>()?;
+	Ok((header, frames))
+}
+
+/// Runs recorded input back through locomotion against the fake backend.
+pub async fn replay(header: &RecordingHeader, frames: &[FrameRecord]) -> Vec<FrameRecord> {
+	let mut locomotion = Locomotion::new(FakeSpatialTree::new(), Some(header.origins()));
+	*locomotion.gears_mut() = header.gears();
+	locomotion.set_bounds(header.bounds);
+	locomotion.set_lift(header.seated_lift).await;
+	let mut replayed = Vec::with_capacity(frames.len());
+	for frame in frames {
+		if locomotion.current_mode() != frame.mode {
+			locomotion.switch_mode(frame.mode);
+		}
+		locomotion.gears_mut().set_gear(frame.gear);
+		locomotion.gears_mut().set_precision(frame.precision);
+		if frame.brake {
+			locomotion.brake();
+		}
+		locomotion.set_pivot(frame.pivot.map(Vec3::from));
+		locomotion.update_velocity(frame.delta_secs, frame.sample());
+		let mut record = FrameRecord {
+			brake: frame.brake,
+			pivot: frame.pivot,
+			..FrameRecord::new(
+				frame.delta_secs,
+				frame.mode,
+				frame.sample(),
+				locomotion.gears(),
+				locomotion.velocity(),
+			)
+		};
+		record.offset = locomotion.apply_offset(frame.delta_secs).await.into();
+		replayed.push(record);
+	}
+	replayed
+}
+
+/// Replays `input`, optionally writes the result to `output`, and logs how far it diverged.
+pub async fn replay_file(input: &Path, output: Option<&Path>) -> io::Result<()> {
+	let (header, recorded) = read_recording(input)?;
+	let replayed = replay(&header, &recorded).await;
+	if let Some(output) = output {
+		let mut recorder = Recorder::create(output, &header)?;
+		for frame in &replayed {
+			recorder.record(frame)?;
+		}
+	}
+
+	let max_velocity_error = recorded
+		.iter()
+		.zip(&replayed)
+		.map(|(recorded, replayed)| {
+			Vec3::from(recorded.velocity).distance(Vec3::from(replayed.velocity))
+		})
+		.fold(0.0, f32::max);
+	let total_offset = |frames: &[FrameRecord]| -> Vec3 {
+		frames.iter().map(|frame| Vec3::from(frame.offset)).sum()
+	};
+	info!(
+		frames = replayed.len(),
+		max_velocity_error,
+		recorded_offset = ?total_offset(&recorded),
+		replayed_offset = ?total_offset(&replayed),
+		"replay finished"
+	);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::Vec3;
+
+	use super::{FrameRecord, RecordingHeader, replay};
+	use crate::{
+		backend::OriginPose,
+		bounds::{Bounds, BoundsShape},
+		locomotion::{Gears, Mode, WaftSample},
+	};
+
+	fn block_on<F: Future>(future: F) -> F::Output {
+		tokio::runtime::Builder::new_current_thread()
+			.build()
+			.unwrap()
+			.block_on(future)
+	}
+
+	/// A second of wafting along +X, then a second of coasting.
+	fn waft_frames(gears: &Gears) -> Vec<FrameRecord> {
+		(0..180)
+			.map(|frame| {
+				let sample = WaftSample {
+					position: (frame < 90).then(|| Vec3::X * frame as f32 * 0.01),
+					thrusting: frame < 90,
+					..Default::default()
+				};
+				FrameRecord::new(1.0 / 90.0, Mode::MonadoOffset, sample, gears, Vec3::ZERO)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn replay_reproduces_recorded_velocity() {
+		let frames = waft_frames(&Gears::default());
+		let recorded = block_on(replay(&RecordingHeader::default(), &frames));
+		let line = serde_json::to_string(&recorded[89]).unwrap();
+		let parsed: FrameRecord = serde_json::from_str(&line).unwrap();
+		assert_eq!(parsed.velocity, recorded[89].velocity);
+
+		let replayed = block_on(replay(&RecordingHeader::default(), &recorded));
+		for (recorded, replayed) in recorded.iter().zip(&replayed) {
+			assert_eq!(recorded.velocity, replayed.velocity);
+			assert_eq!(recorded.offset, replayed.offset);
+		}
+		assert!(recorded[179].offset[0] < 0.0);
+	}
+
+	#[test]
+	fn replay_applies_recorded_settings() {
+		let gears = Gears::new(vec![0.5, 3.0], 1, 0.1);
+		let mut header = RecordingHeader {
+			bounds: Some(Bounds {
+				shape: BoundsShape::Sphere { radius: 5.0 },
+				center: [0.0; 3],
+				soft_edge: 1.0,
+				stiffness: 4.0,
+			}),
+			seated_lift: 0.3,
+			..RecordingHeader::new(&gears)
+		};
+		header.set_start(OriginPose {
+			position: Vec3::X * 6.0,
+			..Default::default()
+		});
+		let line = serde_json::to_string(&header).unwrap();
+		let header: RecordingHeader = serde_json::from_str(&line).unwrap();
+
+		let frames = waft_frames(&gears);
+		let replayed = block_on(replay(&header, &frames));
+		let default = block_on(replay(&RecordingHeader::default(), &frames));
+		assert!((replayed[89].velocity[0] / default[89].velocity[0] - 3.0).abs() < 1e-3);
+
+		// starting outside the bounds pushes back in on top of the wafting
+		let unbounded = RecordingHeader {
+			bounds: None,
+			..header.clone()
+		};
+		let unbounded = block_on(replay(&unbounded, &frames));
+		let total_x =
+			|frames: &[FrameRecord]| frames.iter().map(|frame| frame.offset[0]).sum::<f32>();
+		assert!(total_x(&replayed) < total_x(&unbounded));
+	}
+}
@@ -3,90 +3,292 @@ use std::{collections::HashMap, sync::Arc};
 use glam::{Affine3A, Vec3};
 use stardust_xr_fusion::{
 	ClientHandle,
+	fields::{Field, FieldRefAspect as _},
 	node::NodeResult,
-	objects::{ObjectInfo, interfaces::ReparentableProxy, object_registry::ObjectRegistry},
+	objects::{
+		ObjectInfo, SpatialRefProxyExt as _,
+		interfaces::{ReparentableProxy, SpatialRefProxy},
+		object_registry::ObjectRegistry,
+	},
 	query::{ObjectQuery, QueryEvent},
 	spatial::{Spatial, SpatialAspect, SpatialRef, SpatialRefAspect, Transform},
 };
 use stardust_xr_molecules::dbus::AbortOnDrop;
 use tracing::error;
 
-use crate::solar_sailer::mat_from_transform;
+use crate::{
+	backend::{BackendError, BackendResult, SpatialTree},
+	bounds::{Bounds, Edge},
+	solar_sailer::mat_from_transform,
+	theme::Stroke,
+	zone::{Zone, ZoneShape},
+};
 
-pub struct ReparentMovement {
-	spatial: Spatial,
-	spatial_id: u64,
-	reparenting: Option<AbortOnDrop>,
-	obj_reg: Arc<ObjectRegistry>,
+pub struct ReparentMovement<S> {
+	tree: S,
+	bounds: Option<Bounds>,
+	edge: Option<Edge>,
 }
 
-impl ReparentMovement {
-	pub async fn apply_offset(
-		&mut self,
-		delta_secs: f32,
-		velocity_ref: &SpatialRef,
-		velocity: Vec3,
-	) {
-		if self.reparenting.is_none() {
-			self.reparenting = Some(AbortOnDrop(
-				tokio::spawn(Self::reparent_task(self.spatial_id, self.obj_reg.clone()))
-					.abort_handle(),
-			));
+impl<S: SpatialTree> ReparentMovement<S> {
+	pub fn new(tree: S) -> Self {
+		ReparentMovement {
+			tree,
+			bounds: None,
+			edge: None,
 		}
+	}
 
-		let mat = mat_from_transform(&velocity_ref.get_transform(&self.spatial).await.unwrap());
-		let movement = mat.transform_vector3(velocity * delta_secs);
+	pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+		self.bounds = bounds;
+		self.edge = None;
+	}
+
+	/// Closest edge of the bounds as of the last movement, normal in velocity space.
+	pub fn edge(&self) -> Option<Edge> {
+		self.edge
+	}
+
+	/// Keeps every `Reparentable` on the world root until `end_session`.
+	pub fn start_session(&mut self) {
+		self.tree.begin_reparenting();
+	}
+	pub fn end_session(&mut self) {
+		self.tree.end_reparenting();
+	}
+
+	/// Returns how far the world root moved, in its own space.
+	pub async fn apply_offset(&mut self, delta_secs: f32, velocity: Vec3) -> Vec3 {
+		let Ok(mat) =
+			self.tree.velocity_to_world().await.inspect_err(|err| {
+				error!("unable to get velocity_ref to spatial transform: {err}")
+			})
+		else {
+			return Vec3::ZERO;
+		};
+		// we move the other way relative to the world root
+		let (user_movement, edge) = Bounds::constrain_in(
+			self.bounds.as_ref(),
+			mat,
+			mat.translation.into(),
+			-mat.transform_vector3(velocity * delta_secs),
+			delta_secs,
+		);
+		self.edge = edge;
+		let movement = -user_movement;
 		let offset = Affine3A::from_translation(movement);
-		if let Err(err) = self.spatial.set_relative_transform(
-			velocity_ref,
-			Transform::from_translation((offset * mat.inverse()).to_scale_rotation_translation().2),
-		) {
+		if let Err(err) = self
+			.tree
+			.set_world_translation((offset * mat.inverse()).to_scale_rotation_translation().2)
+		{
 			error!("unable to set transform: {err}");
 		}
+		movement
 	}
 
-	pub fn stopped_moving(&mut self) {
-		self.reparenting.take();
+	/// Translation of the world root relative to the velocity reference space.
+	pub async fn placement(&mut self) -> Option<Vec3> {
+		self.tree
+			.velocity_to_world()
+			.await
+			.inspect_err(|err| error!("unable to get velocity_ref to spatial transform: {err}"))
+			.ok()
+			.map(|mat| mat.inverse().translation.into())
 	}
+	pub fn place(&mut self, translation: Vec3) {
+		if let Err(err) = self.tree.set_world_translation(translation) {
+			error!("unable to set transform: {err}");
+		}
+	}
+
+	#[cfg(test)]
+	pub fn spatial_tree(&self) -> &S {
+		&self.tree
+	}
+}
 
+/// The world root spatial on the server, with every `Reparentable` attached for the session.
+///
+/// Objects are parented once as they appear, and unparented when they stop matching.
+/// One being grabbed withdraws its `Reparentable`, which hands it back until it's
+/// released and shows up again.
+///
+/// A zone tree only takes the objects whose origin is inside its field as they appear,
+/// so one dropped into the zone joins it.
+pub struct StardustSpatialTree {
+	spatial: Spatial,
+	spatial_id: u64,
+	velocity_ref: SpatialRef,
+	/// Only we move the world root, so this stays valid between our own moves.
+	velocity_to_world: Option<Affine3A>,
+	reparenting: Option<AbortOnDrop>,
+	obj_reg: Arc<ObjectRegistry>,
+	client: Arc<ClientHandle>,
+	zone: Option<Zone>,
+}
+
+impl StardustSpatialTree {
 	pub async fn new(client: &Arc<ClientHandle>, obj_reg: Arc<ObjectRegistry>) -> NodeResult<Self> {
 		let spatial = Spatial::create(client.get_root(), Transform::identity())?;
 		let spatial_id = spatial.export_spatial().await?;
-		Ok(ReparentMovement {
+		Ok(StardustSpatialTree {
 			spatial,
 			spatial_id,
+			velocity_ref: client.get_root().clone().as_spatial_ref(),
+			velocity_to_world: None,
 			obj_reg,
 			reparenting: None,
+			client: client.clone(),
+			zone: None,
 		})
 	}
 
+	/// A tree that only moves what's inside a `shape` around its root.
+	pub async fn zone(
+		client: &Arc<ClientHandle>,
+		obj_reg: Arc<ObjectRegistry>,
+		shape: ZoneShape,
+		stroke: Stroke,
+	) -> NodeResult<Self> {
+		let mut tree = Self::new(client, obj_reg).await?;
+		tree.zone = Some(Zone::create(&tree.spatial, shape, stroke)?);
+		Ok(tree)
+	}
+
 	async fn reparent_task(spatial_id: u64, obj_reg: Arc<ObjectRegistry>) {
 		let mut reparented = ReparentedSpatials::default();
 		let mut query = ObjectQuery::<ReparentableProxy, ()>::new(obj_reg, ());
 		while let Some(e) = query.recv_event().await {
 			match e {
-				QueryEvent::NewMatch(object_info, proxy) => {
-					if proxy.parent(spatial_id).await.is_ok() {
-						reparented.0.insert(object_info, proxy);
+				QueryEvent::NewMatch(object_info, proxy) => match proxy.parent(spatial_id).await {
+					Ok(()) => _ = reparented.0.insert(object_info, proxy),
+					Err(err) => error!("unable to reparent object: {err}"),
+				},
+				QueryEvent::MatchLost(object_info) => reparented.release(&object_info),
+				_ => {}
+			}
+		}
+	}
+
+	async fn zone_task(
+		spatial_id: u64,
+		obj_reg: Arc<ObjectRegistry>,
+		client: Arc<ClientHandle>,
+		field: Field,
+	) {
+		let mut reparented = ReparentedSpatials::default();
+		let mut query =
+			ObjectQuery::<(ReparentableProxy<'static>, SpatialRefProxy<'static>), ()>::new(
+				obj_reg,
+				(),
+			);
+		while let Some(e) = query.recv_event().await {
+			match e {
+				QueryEvent::NewMatch(object_info, (proxy, spatial_ref)) => {
+					let Some(spatial) = spatial_ref.import(&client).await else {
+						continue;
+					};
+					match field.distance(&spatial, [0.0; 3]).await {
+						Ok(distance) if distance <= 0.0 => {}
+						Ok(_) => continue,
+						Err(err) => {
+							error!("unable to check if object is in the zone: {err}");
+							continue;
+						}
+					}
+					match proxy.parent(spatial_id).await {
+						Ok(()) => _ = reparented.0.insert(object_info, proxy),
+						Err(err) => error!("unable to reparent object: {err}"),
 					}
 				}
-				QueryEvent::MatchLost(object_info) => {
-					reparented.0.remove(&object_info);
-				}
+				QueryEvent::MatchLost(object_info) => reparented.release(&object_info),
 				_ => {}
 			}
 		}
 	}
 }
 
+impl SpatialTree for StardustSpatialTree {
+	async fn velocity_to_world(&mut self) -> BackendResult<Affine3A> {
+		if let Some(velocity_to_world) = self.velocity_to_world {
+			return Ok(velocity_to_world);
+		}
+		let velocity_to_world = self
+			.velocity_ref
+			.get_transform(&self.spatial)
+			.await
+			.map(|transform| mat_from_transform(&transform))
+			.map_err(BackendError::new)?;
+		Ok(*self.velocity_to_world.insert(velocity_to_world))
+	}
+
+	fn set_world_translation(&mut self, translation: Vec3) -> BackendResult<()> {
+		let result = self
+			.spatial
+			.set_relative_transform(&self.velocity_ref, Transform::from_translation(translation))
+			.map_err(BackendError::new);
+		// only the translation is set, so the world root keeps whatever rotation it had
+		self.velocity_to_world = match (&result, self.velocity_to_world) {
+			(Ok(()), Some(velocity_to_world)) => {
+				let mut world = velocity_to_world.inverse();
+				world.translation = translation.into();
+				Some(world.inverse())
+			}
+			_ => None,
+		};
+		result
+	}
+
+	fn begin_reparenting(&mut self) {
+		if self.reparenting.is_some() {
+			return;
+		}
+		let task = match &self.zone {
+			Some(zone) => {
+				if let Err(err) = zone.set_visible(true) {
+					error!("unable to show zone: {err}");
+				}
+				tokio::spawn(Self::zone_task(
+					self.spatial_id,
+					self.obj_reg.clone(),
+					self.client.clone(),
+					zone.field.clone(),
+				))
+			}
+			None => tokio::spawn(Self::reparent_task(self.spatial_id, self.obj_reg.clone())),
+		};
+		self.reparenting = Some(AbortOnDrop(task.abort_handle()));
+	}
+
+	fn end_reparenting(&mut self) {
+		self.reparenting.take();
+		if let Some(zone) = &self.zone
+			&& let Err(err) = zone.set_visible(false)
+		{
+			error!("unable to hide zone: {err}");
+		}
+	}
+}
+
 #[derive(Default)]
 struct ReparentedSpatials(HashMap<ObjectInfo, ReparentableProxy<'static>>);
+impl ReparentedSpatials {
+	/// Hands the object back, which fails harmlessly if it's already gone or been grabbed.
+	fn release(&mut self, object_info: &ObjectInfo) {
+		if let Some(proxy) = self.0.remove(object_info) {
+			Self::unparent(proxy);
+		}
+	}
+	fn unparent(proxy: ReparentableProxy<'static>) {
+		tokio::spawn(async move {
+			_ = proxy.unparent().await;
+		});
+	}
+}
 impl Drop for ReparentedSpatials {
 	fn drop(&mut self) {
 		for (_, proxy) in self.0.drain() {
-			tokio::spawn(async move {
-				_ = proxy.unparent().await;
-			});
+			Self::unparent(proxy);
 		}
 	}
 }
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use glam::{Affine3A, Vec3};
+use stardust_xr_fusion::spatial::SpatialRef;
+
+use crate::{backend::BackendResult, pipelined_transform::PipelinedTransform};
+
+/// A spatial the user rides along with, followed from the frame loop.
+pub struct Ride {
+	transform: PipelinedTransform,
+	/// Transform the spatial last moved to and when.
+	last_move: Option<(Affine3A, Instant)>,
+	velocity: Vec3,
+}
+
+impl Ride {
+	/// Not moving for this long counts as stopped.
+	const STILL: Duration = Duration::from_millis(100);
+
+	pub fn new(spatial: SpatialRef, velocity_ref: SpatialRef) -> Self {
+		Ride {
+			transform: PipelinedTransform::new(spatial, velocity_ref),
+			last_move: None,
+			velocity: Vec3::ZERO,
+		}
+	}
+
+	/// Fetches the spatial's transform, in the velocity reference space.
+	pub async fn update(&mut self) -> BackendResult<()> {
+		let transform = self.transform.get().await?;
+		let now = Instant::now();
+		match self.last_move {
+			Some((last, moved)) if last == transform => {
+				if now - moved > Self::STILL {
+					self.velocity = Vec3::ZERO;
+				}
+			}
+			Some((last, moved)) => {
+				self.velocity = Vec3::from(transform.translation - last.translation)
+					/ (now - moved).as_secs_f32();
+				self.last_move = Some((transform, now));
+			}
+			None => self.last_move = Some((transform, now)),
+		}
+		Ok(())
+	}
+
+	pub fn latest(&self) -> Option<Affine3A> {
+		self.last_move.map(|(transform, _)| transform)
+	}
+
+	/// How fast the spatial is moving, in the velocity reference space.
+	pub fn velocity(&self) -> Vec3 {
+		self.velocity
+	}
+}
@@ -0,0 +1,271 @@
+use std::{
+	fs, io,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use glam::{Affine3A, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	drawable::{Lines, LinesAspect as _, Text, TextAspect as _},
+	node::NodeResult,
+	spatial::{SpatialRef, SpatialRefAspect, Transform},
+};
+use stardust_xr_molecules::lines::{LineExt as _, circle};
+use tracing::error;
+
+use crate::{
+	backend::{OriginPose, TrackingOrigin},
+	pipelined_transform::PipelinedTransform,
+	readout::prompt,
+	settings::Settings,
+	theme::{Stroke, Theme},
+};
+
+/// Closer together than this, two touches don't say much about the wall's direction.
+const MIN_SPAN: f32 = 0.2;
+
+/// The stage space correction that puts the wall through `left` and `right` along the
+/// stage's X axis, with `left` above the stage origin and the room toward +Z.
+pub fn wall_alignment(left: Vec3, right: Vec3) -> Option<Affine3A> {
+	let along = (right - left).with_y(0.0);
+	if along.length() < MIN_SPAN {
+		return None;
+	}
+	// only ever about Y, even for a wall running along -X
+	let yaw = Affine3A::from_rotation_y(along.z.atan2(along.x));
+	Some(yaw * Affine3A::from_translation(-left.with_y(0.0)))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedOrigin {
+	pub name: String,
+	pub position: [f32; 3],
+	pub orientation: [f32; 4],
+}
+
+/// Origin offsets that line the stage up with the room, reapplied on startup.
+///
+/// Saved without the seated lift, which is applied on top of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoomAlignment {
+	pub origins: Vec<SavedOrigin>,
+	/// How far floor leveling had raised the saved origins, for walking to keep.
+	#[serde(default)]
+	pub floor: f32,
+}
+
+impl RoomAlignment {
+	fn path() -> Option<PathBuf> {
+		Some(Settings::dir()?.join("room.json"))
+	}
+
+	/// The origins named in `selected`, or every one if it's empty.
+	pub fn new(origins: &[TrackingOrigin], selected: &[String], lift: f32, floor: f32) -> Self {
+		RoomAlignment {
+			origins: origins
+				.iter()
+				.filter(|origin| selected.is_empty() || selected.contains(&origin.name))
+				.map(|origin| SavedOrigin {
+					name: origin.name.clone(),
+					position: (origin.offset.position - Vec3::Y * lift).into(),
+					orientation: origin.offset.orientation.into(),
+				})
+				.collect(),
+			floor,
+		}
+	}
+
+	/// Every saved offset, without any lift.
+	pub fn offsets(&self) -> Vec<(String, OriginPose)> {
+		self.origins
+			.iter()
+			.map(|origin| {
+				(
+					origin.name.clone(),
+					OriginPose {
+						position: origin.position.into(),
+						orientation: Quat::from_array(origin.orientation).normalize(),
+					},
+				)
+			})
+			.collect()
+	}
+
+	/// Nothing if it's never been saved.
+	pub fn load() -> Option<Self> {
+		let path = Self::path()?;
+		match fs::read_to_string(&path) {
+			Ok(room) => serde_json::from_str(&room)
+				.inspect_err(|err| error!("invalid room alignment in {}: {err}", path.display()))
+				.ok(),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+			Err(err) => {
+				error!("unable to read {}: {err}", path.display());
+				None
+			}
+		}
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		let path = Self::path()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		fs::write(path, serde_json::to_string_pretty(self)?)
+	}
+}
+
+/// Has the user double pinch on two points along a wall, left then right, and hands back
+/// the correction lining the stage up with it.
+pub struct WallAlignment {
+	root_to_stage: PipelinedTransform,
+	prompt: Text,
+	markers: Lines,
+	marker: Stroke,
+	/// First touch, in stage space.
+	left: Option<Vec3>,
+	/// The prompt stays empty until the first update, so it can wait on other tools.
+	started: bool,
+	applied: Option<Instant>,
+}
+
+impl WallAlignment {
+	/// How long the result stays up after applying.
+	const LINGER: Duration = Duration::from_secs(3);
+
+	/// `prompt_parent` is best the head, `stage` the play space.
+	pub fn new(
+		prompt_parent: &impl SpatialRefAspect,
+		root: SpatialRef,
+		stage: SpatialRef,
+		theme: &Theme,
+	) -> NodeResult<Self> {
+		Ok(WallAlignment {
+			prompt: prompt(prompt_parent, "")?,
+			markers: Lines::create(&stage, Transform::identity(), &[])?,
+			marker: theme.guide,
+			root_to_stage: PipelinedTransform::new(root, stage),
+			left: None,
+			started: false,
+			applied: None,
+		})
+	}
+
+	/// Still waiting on a touch, rather than showing the result.
+	pub fn awaiting_input(&self) -> bool {
+		self.applied.is_none()
+	}
+
+	/// Done showing the result, so it can be dropped.
+	pub fn finished(&self) -> bool {
+		self.applied
+			.is_some_and(|applied| applied.elapsed() > Self::LINGER)
+	}
+
+	/// `touch` is the tip after a double pinch, in the velocity reference space. Returns the
+	/// stage space correction once both points are in.
+	pub async fn update(&mut self, touch: Option<Vec3>) -> Option<Affine3A> {
+		if self.applied.is_some() {
+			return None;
+		}
+		if !std::mem::replace(&mut self.started, true) {
+			_ = self
+				.prompt
+				.set_text("Face a wall and double pinch\nwith the pen tip on it to your left");
+		}
+		let touch = touch?;
+		let touch = self
+			.root_to_stage
+			.get()
+			.await
+			.inspect_err(|err| error!("unable to get velocity_ref to stage transform: {err}"))
+			.ok()?
+			.transform_point3(touch);
+		let Some(left) = self.left else {
+			self.left = Some(touch);
+			_ = self
+				.prompt
+				.set_text("Now double pinch on the wall\nfurther to your right");
+			let marker = self.marker.draw(
+				circle(32, 0.0, 0.03).transform(Mat4::from_translation(touch)),
+				0.002,
+			);
+			if let Err(err) = self.markers.set_lines(&marker) {
+				error!("unable to show wall marker: {err}");
+			}
+			return None;
+		};
+		let Some(correction) = wall_alignment(left, touch) else {
+			_ = self.prompt.set_text(&format!(
+				"Too close to the first point\ntry at least {} cm further right",
+				(MIN_SPAN * 100.0) as u32
+			));
+			return None;
+		};
+		self.applied = Some(Instant::now());
+		_ = self.markers.set_lines(&[]);
+		_ = self
+			.prompt
+			.set_text("Lined up with the wall and saved\nUndo to put it back for now");
+		Some(correction)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Quat, Vec3};
+
+	use super::{RoomAlignment, wall_alignment};
+	use crate::backend::{OriginPose, TrackingOrigin};
+
+	#[test]
+	fn wall_lands_on_the_x_axis() {
+		// facing +X at a wall 1m away, so left is -Z
+		let left = Vec3::new(1.0, 1.2, -1.0);
+		let right = Vec3::new(1.0, 0.9, 1.0);
+		let correction = wall_alignment(left, right).unwrap();
+		assert!(correction.transform_point3(left).distance(Vec3::Y * 1.2) < 1e-5);
+		let right = correction.transform_point3(right);
+		assert!(
+			(right.x - 2.0).abs() < 1e-5 && right.z.abs() < 1e-5,
+			"{right}"
+		);
+		let user = correction.transform_point3(Vec3::ZERO);
+		assert!(user.z > 0.0, "{user}");
+
+		let backwards = wall_alignment(Vec3::X, Vec3::NEG_X).unwrap();
+		assert!(backwards.transform_vector3(Vec3::Y).distance(Vec3::Y) < 1e-5);
+		assert!(wall_alignment(left, left + Vec3::Y).is_none());
+	}
+
+	#[test]
+	fn saved_offsets_leave_out_the_lift() {
+		let origin = |id, name: &str| TrackingOrigin {
+			id,
+			name: name.to_string(),
+			offset: OriginPose {
+				position: Vec3::new(1.0, 0.5, 2.0),
+				orientation: Quat::from_rotation_y(0.3),
+			},
+		};
+		let room = RoomAlignment::new(
+			&[origin(0, "stage"), origin(1, "local")],
+			&["stage".into()],
+			0.5,
+			0.0,
+		);
+		let offsets = room.offsets();
+		assert_eq!(offsets.len(), 1);
+		assert_eq!(offsets[0].0, "stage");
+		assert_eq!(offsets[0].1.position, Vec3::new(1.0, 0.0, 2.0));
+		assert!(
+			offsets[0]
+				.1
+				.orientation
+				.angle_between(Quat::from_rotation_y(0.3))
+				< 1e-5
+		);
+	}
+}
@@ -0,0 +1,109 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{bounds::Bounds, dock::DockSettings, locomotion::Gears, zone::ZoneShape};
+
+/// User settings, read from `$XDG_CONFIG_HOME/solar-sailer/settings.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	/// Volume locomotion is kept inside of, unbounded if unset.
+	pub bounds: Option<Bounds>,
+	/// Thrust multipliers the pen can shift between.
+	pub gears: Vec<f32>,
+	/// Index into `gears` to start in.
+	pub default_gear: usize,
+	/// Extra thrust multiplier while the precision modifier is held.
+	pub precision_multiplier: f32,
+	/// Where the pen docks when released near the body, never docks if unset.
+	pub pen_dock: Option<DockSettings>,
+	pub accessibility: Accessibility,
+	pub steering: Steering,
+	/// What zone mode places around the pen tip.
+	pub zone: ZoneShape,
+	/// Names of the Monado origins floor leveling and room alignment correct, every one if
+	/// empty.
+	pub calibrated_origins: Vec<String>,
+	/// Signifier colors and line styles: `default`, `colorblind`, `high_contrast`, or the
+	/// name of one in the `themes` directory.
+	pub theme: String,
+	/// Whose calibrated gesture thresholds to use.
+	pub profile: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+	/// Meters to raise the user's eyes by when seated, applied to the Monado origins.
+	pub seated_lift: f32,
+	/// Grabbing the pen also thrusts, so no pinch is needed.
+	pub one_handed: bool,
+	/// Pinching toggles thrust on and off instead of thrusting while held.
+	pub latch_thrust: bool,
+}
+/// How the held pen turns into thrust.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Steering {
+	/// Waft the pen through the air like pulling on the world.
+	#[default]
+	Waft,
+	/// Fly where the pen tip points, faster the harder it's pinched.
+	Point,
+	/// Fly where the user looks, pushed by any pinch, trigger or thumbstick.
+	Gaze {
+		/// Keep to the horizontal so looking down doesn't fly into the floor.
+		#[serde(default)]
+		lock_pitch: bool,
+	},
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings {
+			bounds: None,
+			gears: vec![0.25, 1.0, 4.0, 16.0],
+			default_gear: 1,
+			precision_multiplier: 0.2,
+			pen_dock: None,
+			accessibility: Accessibility::default(),
+			steering: Steering::default(),
+			zone: ZoneShape::default(),
+			calibrated_origins: Vec::new(),
+			theme: "default".to_string(),
+			profile: "default".to_string(),
+		}
+	}
+}
+
+impl Settings {
+	pub fn dir() -> Option<PathBuf> {
+		Some(dirs::config_dir()?.join("solar-sailer"))
+	}
+
+	pub fn gears(&self) -> Gears {
+		Gears::new(
+			self.gears.clone(),
+			self.default_gear,
+			self.precision_multiplier,
+		)
+	}
+
+	pub fn load() -> Self {
+		let Some(path) = Self::dir().map(|dir| dir.join("settings.json")) else {
+			return Settings::default();
+		};
+		match fs::read_to_string(&path) {
+			Ok(settings) => serde_json::from_str(&settings)
+				.inspect_err(|err| error!("invalid settings in {}: {err}", path.display()))
+				.unwrap_or_default(),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Settings::default(),
+			Err(err) => {
+				error!("unable to read {}: {err}", path.display());
+				Settings::default()
+			}
+		}
+	}
+}
@@ -1,23 +1,60 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, path::Path, sync::Arc};
 
 use glam::{Affine3A, Quat, Vec3};
-use libmonado::Monado;
 use stardust_xr_fusion::{
-	ClientHandle, objects::object_registry::ObjectRegistry, spatial::Transform,
+	ClientHandle,
+	objects::{hmd, object_registry::ObjectRegistry, play_space},
+	spatial::{SpatialRef, Transform},
 };
 use tracing::error;
 
 use crate::{
-	input::Input, monado_movement::MonadoMovement, reparentable_movement::ReparentMovement,
+	audio::MotionAudio,
+	backend::OriginPose,
+	bounds::BoundsSignifier,
+	floor::FloorLeveling,
+	input::Input,
+	locomotion::{Gravity, Mode, Motion, MotionTarget, Movement},
+	monado_movement::MonadoOrigins,
+	movement_task::{CallTimer, MovementTask, Step, Timed},
+	recording::{FrameRecord, Recorder, RecordingHeader},
+	reparentable_movement::StardustSpatialTree,
+	ride::Ride,
+	room::{RoomAlignment, WallAlignment},
+	settings::Settings,
+	targets::ObjectTargets,
+	theme::Theme,
 };
 
 pub struct SolarSailer {
-	monado_movement: Option<MonadoMovement>,
-	mode: Mode,
 	input: Input,
-	reparent_movement: ReparentMovement,
-	velocity: Vec3,
-	moving: bool,
+	motion: Motion,
+	mode: Mode,
+	movement: MovementTask,
+	/// Which backends the mode switch can cycle through.
+	has_monado: bool,
+	has_zone: bool,
+	targets: ObjectTargets,
+	/// What orbit mode turns around, in the velocity reference space.
+	pivot: Option<Vec3>,
+	ride: Option<Ride>,
+	velocity_ref: SpatialRef,
+	/// Settings a recording starts with, as of startup.
+	recording_header: RecordingHeader,
+	recorder: Option<Recorder>,
+	recording_frame: Option<FrameRecord>,
+	/// Recorded frames waiting on the backend step that covers their target.
+	unstepped_frames: VecDeque<(u64, FrameRecord)>,
+	braked: bool,
+	bounds_signifier: Option<BoundsSignifier>,
+	audio: Option<MotionAudio>,
+	/// For the calibration tools' markers.
+	theme: Theme,
+	floor_leveling: Option<FloorLeveling>,
+	/// Waits for floor leveling to finish, since both take over the double pinch.
+	wall_alignment: Option<WallAlignment>,
+	/// Monado origins that floor leveling and room alignment correct, every one if empty.
+	calibrated_origins: Vec<String>,
 }
 
 impl SolarSailer {
@@ -25,94 +62,324 @@ impl SolarSailer {
 		client: Arc<ClientHandle>,
 		object_registry: Arc<ObjectRegistry>,
 		input: Input,
+		settings: &Settings,
 	) -> Self {
-		let monado = match Monado::auto_connect() {
-			Ok(v) => Some(v),
-			Err(err) => {
-				error!("Couldn't connect to monado :( {err}");
-				None
-			}
+		let timer = CallTimer::default();
+		let origins = MonadoOrigins::connect(&client)
+			.await
+			.map(|origins| Timed::new(origins, &timer));
+		let targets = ObjectTargets::new(&client, object_registry.clone());
+		let spatial_tree = StardustSpatialTree::new(&client, object_registry.clone())
+			.await
+			.unwrap();
+		let theme = Theme::load(&settings.theme);
+		let zone_tree =
+			StardustSpatialTree::zone(&client, object_registry, settings.zone, theme.zone)
+				.await
+				.inspect_err(|err| error!("unable to create zone: {err}"))
+				.ok();
+
+		let mut movement = Movement::new(Timed::new(spatial_tree, &timer), origins);
+		// before the lift, which goes on top
+		if let Some(room) = RoomAlignment::load() {
+			movement.restore_origins(&room.offsets(), room.floor).await;
+		}
+		if let Some(zone_tree) = zone_tree {
+			movement = movement.with_zone(Timed::new(zone_tree, &timer));
+		}
+		movement.set_bounds(settings.bounds);
+		movement.set_lift(settings.accessibility.seated_lift).await;
+		let mode = movement.current_mode();
+		let (has_monado, has_zone) = (movement.has_monado(), movement.has_zone());
+		let mut motion = Motion::default();
+		*motion.gears_mut() = settings.gears();
+		let mut recording_header = RecordingHeader {
+			bounds: settings.bounds,
+			seated_lift: movement.lift(),
+			..RecordingHeader::new(motion.gears())
 		};
-		let monado_movement = MonadoMovement::from_monado(&client, monado).await;
+		if let Some(origin) = movement
+			.origin_placement()
+			.await
+			.and_then(|origins| origins.into_iter().next())
+		{
+			recording_header.set_start(OriginPose {
+				position: origin.offset.position - Vec3::Y * movement.lift(),
+				..origin.offset
+			});
+		}
+		let bounds_signifier = match settings.bounds {
+			Some(_) => BoundsSignifier::new(&client, theme.guide)
+				.await
+				.inspect_err(|err| error!("unable to create bounds signifier: {err}"))
+				.ok(),
+			None => None,
+		};
+		let audio = MotionAudio::new(&client)
+			.await
+			.inspect_err(|err| error!("unable to create motion audio: {err}"))
+			.ok()
+			.flatten();
 
 		SolarSailer {
-			mode: match monado_movement.is_some() {
-				true => Mode::MonadoOffset,
-				false => Mode::Reparent,
-			},
-			monado_movement,
 			input,
-			reparent_movement: ReparentMovement::new(&client, object_registry)
-				.await
-				.unwrap(),
-			velocity: Vec3::ZERO,
-			moving: false,
+			motion,
+			mode,
+			movement: MovementTask::spawn(movement, timer),
+			has_monado,
+			has_zone,
+			targets,
+			pivot: None,
+			ride: None,
+			velocity_ref: client.get_root().clone().as_spatial_ref(),
+			recording_header,
+			recorder: None,
+			recording_frame: None,
+			unstepped_frames: VecDeque::new(),
+			braked: false,
+			bounds_signifier,
+			audio,
+			theme,
+			floor_leveling: None,
+			wall_alignment: None,
+			calibrated_origins: settings.calibrated_origins.clone(),
 		}
 	}
+	pub fn record_to(&mut self, path: &Path) {
+		self.recorder = Recorder::create(path, &self.recording_header)
+			.inspect_err(|err| error!("unable to create recording {}: {err}", path.display()))
+			.ok();
+	}
+	pub fn start_calibration(&mut self, profile: String) {
+		self.input.start_calibration(profile);
+	}
+	/// Has the user touch the physical floor to level the tracked one with it.
+	pub async fn start_floor_leveling(&mut self, client: &Arc<ClientHandle>, settings: &Settings) {
+		let Some(play_space) = play_space(client).await else {
+			error!("no play space to level the floor of");
+			return;
+		};
+		let root = client.get_root().clone().as_spatial_ref();
+		let prompt_parent = hmd(client).await.unwrap_or_else(|| root.clone());
+		self.floor_leveling = FloorLeveling::new(
+			&prompt_parent,
+			root,
+			play_space.spatial,
+			Gravity::FLOOR + settings.accessibility.seated_lift,
+			&self.theme,
+		)
+		.inspect_err(|err| error!("unable to start floor leveling: {err}"))
+		.ok();
+	}
+	/// Takes over the double pinch while leveling the floor, to confirm the correction.
+	pub async fn update_floor_leveling(&mut self, delta_secs: f32) {
+		let Some(leveling) = &mut self.floor_leveling else {
+			return;
+		};
+		// picks made while the result is up are left for the other tools
+		let confirm = leveling.awaiting_input() && self.input.pick_request().is_some();
+		if let Some(correction) = leveling.update(delta_secs, self.input.tip(), confirm).await {
+			self.movement
+				.level_floor(correction, self.calibrated_origins.clone());
+		}
+		if leveling.finished() {
+			self.floor_leveling = None;
+		}
+	}
+	/// Has the user touch a wall to line the stage up with the room.
+	pub async fn start_wall_alignment(&mut self, client: &Arc<ClientHandle>) {
+		let Some(play_space) = play_space(client).await else {
+			error!("no play space to line up with the room");
+			return;
+		};
+		let root = client.get_root().clone().as_spatial_ref();
+		let prompt_parent = hmd(client).await.unwrap_or_else(|| root.clone());
+		self.wall_alignment =
+			WallAlignment::new(&prompt_parent, root, play_space.spatial, &self.theme)
+				.inspect_err(|err| error!("unable to start room alignment: {err}"))
+				.ok();
+	}
+	pub async fn update_wall_alignment(&mut self) {
+		if self.floor_leveling.is_some() {
+			return;
+		}
+		let Some(alignment) = &mut self.wall_alignment else {
+			return;
+		};
+		// picks made while the result is up are left for the other tools
+		let touch = match alignment.awaiting_input() {
+			true => self.input.pick_request(),
+			false => None,
+		};
+		if let Some(correction) = alignment.update(touch).await {
+			self.movement
+				.align_room(correction, self.calibrated_origins.clone());
+		}
+		if alignment.finished() {
+			self.wall_alignment = None;
+		}
+	}
+	pub fn should_quit(&self) -> bool {
+		self.input.derezzed()
+	}
 	pub fn should_switch_mode(&mut self) -> bool {
 		self.input.update_mode()
 	}
 	pub fn handle_input(&mut self) {
 		self.input.handle_input();
-	}
-	pub async fn apply_offset(&mut self, delta_secs: f32) {
-		let vel_ref = &self.input.get_velocity_space();
-		let fast_enough = self.velocity.length_squared() > 0.0005;
-		if self.moving && !fast_enough {
-			match self.mode {
-				Mode::Reparent => self.reparent_movement.stopped_moving(),
-				Mode::MonadoOffset => {}
-				Mode::Disabled => {}
+		if let Some(shift) = self.input.gear_shift() {
+			self.motion.gears_mut().shift(shift);
+		}
+		let precision = self.input.precision();
+		self.motion.gears_mut().set_precision(precision);
+		if let Some(action) = self.input.history_action() {
+			// the flight takes over, so don't coast off again after it
+			self.motion.stop();
+			self.ride = None;
+			self.movement.history(action);
+		}
+		self.braked = self.input.brake();
+		if self.braked {
+			self.motion.brake();
+			if let Some(audio) = &self.audio {
+				audio.braked();
 			}
 		}
-		if fast_enough {
-			match (&self.mode, self.monado_movement.as_mut()) {
-				(Mode::MonadoOffset, Some(monado)) => {
-					monado
-						.apply_offset(delta_secs, vel_ref, self.velocity)
-						.await
-				}
-				(Mode::Reparent, _) => {
-					self.reparent_movement
-						.apply_offset(delta_secs, vel_ref, self.velocity)
-						.await
+	}
+	/// Keeps the pen's body dock with the user and flies a summoned pen to them.
+	pub async fn update_pen(&mut self) {
+		self.input.update_pen().await;
+	}
+	/// Picks the orbit pivot under the pen in orbit mode, places the zone around it in zone
+	/// mode, otherwise starts or stops riding along with the object under it.
+	pub async fn update_pick(&mut self) {
+		let Some(tip) = self.input.pick_request() else {
+			return;
+		};
+		match self.mode {
+			Mode::Orbit => {
+				let closest = self.targets.closest(tip).await;
+				self.pivot = Some(closest.map_or(tip, |(_, origin)| origin));
+			}
+			Mode::Zone => self.movement.place_zone(tip),
+			// the ridden object moves along with the world there
+			Mode::Reparent | Mode::Disabled => {}
+			_ if self.ride.is_some() => self.detach(),
+			_ => {
+				if let Some((spatial, _)) = self.targets.closest(tip).await {
+					self.ride = Some(Ride::new(spatial, self.velocity_ref.clone()));
 				}
-				_ => {}
 			}
 		}
 	}
+	pub async fn update_ride(&mut self) {
+		let Some(ride) = &mut self.ride else {
+			return;
+		};
+		if let Err(err) = ride.update().await {
+			error!("lost track of the object being ridden: {err}");
+			self.detach();
+		}
+	}
+	/// Stops riding along, carrying on at the object's speed.
+	fn detach(&mut self) {
+		if let Some(ride) = self.ride.take() {
+			// velocity is how the world moves past us
+			self.motion.add_velocity(-ride.velocity());
+		}
+	}
+	/// Hands the frame's velocity to the movement task without waiting on the backend.
+	pub fn apply_offset(&mut self, delta_secs: f32) {
+		let target = self.movement.set_target(MotionTarget {
+			mode: self.mode,
+			delta_secs,
+			velocity: self.motion.velocity(),
+			thrust: self.motion.thrust(),
+			orbit: self.pivot.and_then(|pivot| self.motion.orbit(pivot)),
+			ride: self.ride.as_ref().and_then(Ride::latest),
+		});
+		if let Some(frame) = self.recording_frame.take() {
+			self.unstepped_frames.push_back((target, frame));
+		}
+		for step in self.movement.finished_steps() {
+			self.record_step(step);
+		}
+	}
+	/// Writes the frames whose targets `step` covered. Coalesced frames record no offset of
+	/// their own, so the offsets still add up to how far the backend moved.
+	fn record_step(&mut self, step: Step) {
+		while let Some((target, _)) = self.unstepped_frames.front()
+			&& *target <= step.target
+		{
+			let (target, mut frame) = self.unstepped_frames.pop_front().unwrap();
+			if target == step.target {
+				frame.offset = step.offset.into();
+			}
+			let Some(recorder) = &mut self.recorder else {
+				continue;
+			};
+			if let Err(err) = recorder.record(&frame) {
+				error!("unable to write recording, stopping: {err}");
+				self.recorder = None;
+			}
+		}
+	}
+
+	/// Leaves the Monado origins without the seated lift.
+	pub async fn shutdown(&mut self) {
+		self.movement.shutdown(self.mode).await;
+	}
 
-	pub fn current_mode(&self) -> Mode {
-		self.mode
+	/// Next in the mode switch cycle, among the modes there's a backend for.
+	pub fn next_mode(&self) -> Mode {
+		self.mode.next(self.has_monado, self.has_zone)
 	}
 
 	pub fn switch_mode(&mut self, mode: Mode) {
-		match self.mode {
-			Mode::Reparent => self.reparent_movement.stopped_moving(),
-			Mode::MonadoOffset => {}
-			Mode::Disabled => {}
+		if matches!(mode, Mode::Reparent | Mode::Zone | Mode::Disabled) {
+			self.detach();
 		}
 		self.mode = mode;
+		if let Some(audio) = &self.audio {
+			audio.mode_switched();
+		}
 	}
 
 	pub async fn update_velocity(&mut self, delta_secs: f32) {
-		let offset = self.input.waft(delta_secs).await;
-		self.velocity *= 0.99;
-		self.velocity += offset;
+		let sample = self.input.sample_waft().await;
+		self.motion.update_velocity(delta_secs, sample);
+		if self.recorder.is_some() {
+			self.recording_frame = Some(FrameRecord {
+				brake: self.braked,
+				pivot: self.pivot.map(Into::into),
+				..FrameRecord::new(
+					delta_secs,
+					self.mode,
+					sample,
+					self.motion.gears(),
+					self.motion.velocity(),
+				)
+			});
+		}
 	}
-	pub fn update_signifiers(&self) {
-		self.input.update_signifiers(self.mode);
+	pub async fn update_signifiers(&mut self) {
+		let edge = self.movement.status().edge;
+		self.input.update_signifiers(
+			self.mode,
+			self.motion.gears(),
+			self.motion.velocity().length(),
+			edge,
+		);
+		if let Some(bounds_signifier) = &mut self.bounds_signifier {
+			bounds_signifier.update(edge).await;
+		}
+		if let Some(audio) = &mut self.audio {
+			audio.update(self.motion.velocity().length());
+			audio.update_limit(edge);
+		}
 	}
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
-pub enum Mode {
-	Reparent,
-	MonadoOffset,
-	Disabled,
-}
-
 pub fn mat_from_transform(transform: &Transform) -> Affine3A {
 	Affine3A::from_scale_rotation_translation(
 		transform.scale.map(Vec3::from).unwrap_or(Vec3::ONE),
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+use stardust_xr_fusion::zbus::{self, Connection, MatchRule, MessageStream, message::Type};
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt as _;
+
+/// Where a summoned pen flies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummonTarget {
+	/// Relative to the input handler.
+	Point(Vec3),
+	/// In front of the user's face, for summons that didn't come from a hand.
+	Head,
+}
+
+/// The pen flying back to the user.
+#[derive(Debug, Clone, Copy)]
+pub struct Summon {
+	pub target: SummonTarget,
+	/// Where the pen was when the summon started, relative to the input handler.
+	pub start: Option<Vec3>,
+	started: Instant,
+}
+
+impl Summon {
+	const DURATION: Duration = Duration::from_millis(400);
+
+	pub fn new(target: SummonTarget) -> Self {
+		Summon {
+			target,
+			start: None,
+			started: Instant::now(),
+		}
+	}
+
+	/// Where the pen should be on its way from `start` to `target`, and whether it arrived.
+	pub fn position(&self, start: Vec3, target: Vec3) -> (Vec3, bool) {
+		Self::ease(start, target, self.started.elapsed())
+	}
+
+	fn ease(start: Vec3, target: Vec3, elapsed: Duration) -> (Vec3, bool) {
+		let t = (elapsed.as_secs_f32() / Self::DURATION.as_secs_f32()).min(1.0);
+		// ease out cubic, so the pen slows down as it reaches the hand
+		let eased = 1.0 - (1.0 - t).powi(3);
+		(start.lerp(target, eased), t >= 1.0)
+	}
+}
+
+/// Lets other clients bring the pen back to the user by emitting
+/// `org.stardustxr.SolarSailer.Pen.Summon`, which flies it to just in front of them.
+pub async fn listen_for_summons(
+	connection: &Connection,
+	sender: mpsc::UnboundedSender<()>,
+) -> zbus::Result<()> {
+	let rule = MatchRule::builder()
+		.msg_type(Type::Signal)
+		.interface("org.stardustxr.SolarSailer.Pen")?
+		.member("Summon")?
+		.build();
+	let mut summons = MessageStream::for_match_rule(rule, connection, None).await?;
+	tokio::spawn(async move {
+		while let Some(summon) = summons.next().await {
+			if summon.is_ok() && sender.send(()).is_err() {
+				break;
+			}
+		}
+	});
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use glam::Vec3;
+
+	use super::Summon;
+
+	#[test]
+	fn summon_eases_out_and_arrives() {
+		let (start, target) = (Vec3::ZERO, Vec3::X);
+		let (halfway, arrived) = Summon::ease(start, target, Summon::DURATION / 2);
+		assert!(!arrived && halfway.x > 0.5);
+		assert_eq!(
+			Summon::ease(start, target, Summon::DURATION + Duration::from_millis(1)),
+			(target, true)
+		);
+	}
+}
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+
+use glam::Vec3;
+use stardust_xr_fusion::{
+	ClientHandle,
+	objects::{
+		ObjectInfo, SpatialRefProxyExt as _,
+		interfaces::{ReparentableProxy, SpatialRefProxy},
+		object_registry::ObjectRegistry,
+	},
+	query::{ObjectQuery, QueryEvent},
+	spatial::{SpatialRef, SpatialRefAspect as _},
+};
+use stardust_xr_molecules::dbus::AbortOnDrop;
+
+/// The spatial of every `Reparentable` object, to pick the one under the pen.
+pub struct ObjectTargets {
+	spatials: Rc<RefCell<HashMap<ObjectInfo, SpatialRef>>>,
+	velocity_ref: SpatialRef,
+	_query: AbortOnDrop,
+}
+
+impl ObjectTargets {
+	/// How far an object's origin can be from the pen tip to pick it.
+	const SNAP_DISTANCE: f32 = 0.3;
+
+	pub fn new(client: &Arc<ClientHandle>, obj_reg: Arc<ObjectRegistry>) -> Self {
+		let spatials = Rc::default();
+		let query = tokio::task::spawn_local(Self::query_task(
+			client.clone(),
+			obj_reg,
+			Rc::clone(&spatials),
+		));
+		ObjectTargets {
+			spatials,
+			velocity_ref: client.get_root().clone().as_spatial_ref(),
+			_query: AbortOnDrop(query.abort_handle()),
+		}
+	}
+
+	async fn query_task(
+		client: Arc<ClientHandle>,
+		obj_reg: Arc<ObjectRegistry>,
+		spatials: Rc<RefCell<HashMap<ObjectInfo, SpatialRef>>>,
+	) {
+		let mut query =
+			ObjectQuery::<(ReparentableProxy<'static>, SpatialRefProxy<'static>), ()>::new(
+				obj_reg,
+				(),
+			);
+		while let Some(e) = query.recv_event().await {
+			match e {
+				QueryEvent::NewMatch(object_info, (_, spatial_ref)) => {
+					if let Some(spatial) = spatial_ref.import(&client).await {
+						spatials.borrow_mut().insert(object_info, spatial);
+					}
+				}
+				QueryEvent::MatchLost(object_info) => {
+					spatials.borrow_mut().remove(&object_info);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	/// The object whose origin is closest to `tip`, and that origin, if any are close.
+	/// Both in the velocity reference space.
+	pub async fn closest(&self, tip: Vec3) -> Option<(SpatialRef, Vec3)> {
+		let spatials = self.spatials.borrow().values().cloned().collect::<Vec<_>>();
+		let mut closest: Option<(SpatialRef, Vec3, f32)> = None;
+		for spatial in spatials {
+			let Ok(transform) = spatial.get_transform(&self.velocity_ref).await else {
+				continue;
+			};
+			let origin = transform.translation.map(Vec3::from).unwrap_or_default();
+			let distance = origin.distance(tip);
+			if distance < Self::SNAP_DISTANCE
+				&& closest
+					.as_ref()
+					.is_none_or(|(_, _, closest)| distance < *closest)
+			{
+				closest = Some((spatial, origin, distance));
+			}
+		}
+		closest.map(|(spatial, origin, _)| (spatial, origin))
+	}
+}
@@ -0,0 +1,371 @@
+use std::{fmt, fs, io, path::PathBuf};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	drawable::{Line, LinePoint},
+	values::{Color, color::rgba},
+};
+use tracing::error;
+
+use crate::{locomotion::Mode, settings::Settings};
+
+/// An sRGB color written as `#rrggbb` or `#rrggbbaa`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ThemeColor(pub [f32; 4]);
+impl ThemeColor {
+	const fn rgb(r: u8, g: u8, b: u8) -> Self {
+		ThemeColor([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0])
+	}
+
+	pub fn with_alpha(self, alpha: f32) -> Self {
+		let [r, g, b, _] = self.0;
+		ThemeColor([r, g, b, alpha])
+	}
+
+	pub fn linear(self) -> Color {
+		let [r, g, b, a] = self.0;
+		rgba!(r, g, b, a).to_linear()
+	}
+}
+impl TryFrom<String> for ThemeColor {
+	type Error = String;
+
+	fn try_from(hex: String) -> Result<Self, Self::Error> {
+		let digits = hex.strip_prefix('#').unwrap_or(&hex);
+		if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+			return Err(format!("{hex:?} isn't a #rrggbb or #rrggbbaa color"));
+		}
+		let mut channels = [1.0; 4];
+		for (channel, i) in channels.iter_mut().zip((0..digits.len()).step_by(2)) {
+			*channel = u8::from_str_radix(&digits[i..i + 2], 16)
+				.map_err(|err| format!("{hex:?} isn't a color: {err}"))? as f32
+				/ 255.0;
+		}
+		Ok(ThemeColor(channels))
+	}
+}
+impl From<ThemeColor> for String {
+	fn from(color: ThemeColor) -> Self {
+		color.to_string()
+	}
+}
+impl fmt::Display for ThemeColor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let [r, g, b, a] = self
+			.0
+			.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+		write!(f, "#{r:02x}{g:02x}{b:02x}")?;
+		if a != 255 {
+			write!(f, "{a:02x}")?;
+		}
+		Ok(())
+	}
+}
+
+/// How a signifier is drawn in one state, with cues besides color so no state relies on
+/// telling colors apart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+	pub color: ThemeColor,
+	/// Multiplies the signifier's usual thickness.
+	#[serde(default = "Stroke::default_thickness")]
+	pub thickness: f32,
+	/// Gaps broken into the line, solid if 0.
+	#[serde(default)]
+	pub dashes: u32,
+}
+impl Stroke {
+	const fn new(color: ThemeColor, thickness: f32, dashes: u32) -> Self {
+		Stroke {
+			color,
+			thickness,
+			dashes,
+		}
+	}
+	fn default_thickness() -> f32 {
+		1.0
+	}
+
+	pub fn with_alpha(self, alpha: f32) -> Self {
+		Stroke {
+			color: self.color.with_alpha(alpha),
+			..self
+		}
+	}
+
+	/// A line through `points`, drawn like `draw`.
+	pub fn polyline(
+		&self,
+		points: impl IntoIterator<Item = Vec3>,
+		cyclic: bool,
+		thickness: f32,
+	) -> Vec<Line> {
+		let color = self.color.linear();
+		let line = Line {
+			points: points
+				.into_iter()
+				.map(|point| LinePoint {
+					point: point.into(),
+					thickness,
+					color,
+				})
+				.collect(),
+			cyclic,
+		};
+		self.draw(line, thickness)
+	}
+
+	/// `line` in this stroke's color, `thickness` times its own, split into dashes.
+	pub fn draw(&self, line: Line, thickness: f32) -> Vec<Line> {
+		let color = self.color.linear();
+		let mut points = line
+			.points
+			.into_iter()
+			.map(|point| LinePoint {
+				point: point.point,
+				thickness: thickness * self.thickness,
+				color,
+			})
+			.collect::<Vec<_>>();
+		if self.dashes == 0 || points.len() < 2 {
+			return vec![Line {
+				points,
+				cyclic: line.cyclic,
+			}];
+		}
+		// the closing segment gets dashed like the rest
+		if line.cyclic {
+			points.push(LinePoint {
+				point: points[0].point,
+				thickness: points[0].thickness,
+				color,
+			});
+		}
+		let segments = points.len() - 1;
+		let drawn = |segment: usize| {
+			((segment as f32 + 0.5) * self.dashes as f32 / segments as f32).fract() < 0.5
+		};
+		let mut dashes = Vec::new();
+		let mut dash: Vec<LinePoint> = Vec::new();
+		for (segment, pair) in points.windows(2).enumerate() {
+			if !drawn(segment) {
+				if dash.len() > 1 {
+					dashes.push(std::mem::take(&mut dash));
+				}
+				dash.clear();
+				continue;
+			}
+			if dash.is_empty() {
+				dash.push(LinePoint { ..pair[0] });
+			}
+			dash.push(LinePoint { ..pair[1] });
+		}
+		if dash.len() > 1 {
+			dashes.push(dash);
+		}
+		dashes
+			.into_iter()
+			.map(|points| Line {
+				points,
+				cyclic: false,
+			})
+			.collect()
+	}
+}
+
+/// Colors and line styles of every signifier, picked by name in the settings.
+///
+/// `default`, `colorblind` and `high_contrast` are built in. Any other name is read from
+/// `themes/<name>.json` in the settings directory, with anything left out taken from
+/// `default`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+	pub reparent: Stroke,
+	pub monado_offset: Stroke,
+	pub walk: Stroke,
+	pub orbit: Stroke,
+	pub zone: Stroke,
+	pub disabled: Stroke,
+	/// The pen or hand while moving, and the pen's tip glow.
+	pub grabbing: Stroke,
+	/// Bounds, the zone outline and calibration markers.
+	pub guide: Stroke,
+	/// Where calibration will put things once applied.
+	pub target: Stroke,
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		Theme {
+			reparent: Stroke::new(ThemeColor([0.015686, 0.992157, 0.298039, 1.0]), 1.0, 0),
+			monado_offset: Stroke::new(ThemeColor([0.361, 0.161, 0.514, 1.0]), 1.0, 12),
+			walk: Stroke::new(ThemeColor([0.937, 0.424, 0.0, 1.0]), 1.0, 6),
+			orbit: Stroke::new(ThemeColor([0.0, 0.745, 0.878, 1.0]), 1.0, 24),
+			zone: Stroke::new(ThemeColor([0.984, 0.773, 0.192, 1.0]), 1.5, 0),
+			disabled: Stroke::new(ThemeColor([0.2, 0.2, 0.2, 1.0]), 0.5, 0),
+			grabbing: Stroke::new(ThemeColor([0.0, 0.549, 1.0, 1.0]), 2.0, 0),
+			guide: Stroke::new(ThemeColor([1.0, 1.0, 1.0, 1.0]), 1.0, 0),
+			target: Stroke::new(ThemeColor([0.0, 1.0, 0.735, 1.0]), 1.0, 8),
+		}
+	}
+}
+
+impl Theme {
+	/// Okabe and Ito's palette, which stays apart with any common color vision deficiency.
+	pub fn colorblind() -> Self {
+		Theme {
+			reparent: Stroke::new(ThemeColor::rgb(0x00, 0x9e, 0x73), 1.0, 0),
+			monado_offset: Stroke::new(ThemeColor::rgb(0xcc, 0x79, 0xa7), 1.0, 12),
+			walk: Stroke::new(ThemeColor::rgb(0xe6, 0x9f, 0x00), 1.0, 6),
+			orbit: Stroke::new(ThemeColor::rgb(0x56, 0xb4, 0xe9), 1.0, 24),
+			zone: Stroke::new(ThemeColor::rgb(0xf0, 0xe4, 0x42), 1.5, 0),
+			grabbing: Stroke::new(ThemeColor::rgb(0x00, 0x72, 0xb2), 2.0, 0),
+			target: Stroke::new(ThemeColor::rgb(0xd5, 0x5e, 0x00), 1.0, 8),
+			..Theme::default()
+		}
+	}
+
+	/// Bright on dark with thicker lines, for low vision or busy surroundings.
+	pub fn high_contrast() -> Self {
+		let white = ThemeColor::rgb(0xff, 0xff, 0xff);
+		let yellow = ThemeColor::rgb(0xff, 0xff, 0x00);
+		let cyan = ThemeColor::rgb(0x00, 0xff, 0xff);
+		Theme {
+			reparent: Stroke::new(white, 2.0, 0),
+			monado_offset: Stroke::new(yellow, 2.0, 12),
+			walk: Stroke::new(yellow, 2.0, 6),
+			orbit: Stroke::new(cyan, 2.0, 24),
+			zone: Stroke::new(cyan, 3.0, 0),
+			disabled: Stroke::new(ThemeColor::rgb(0x80, 0x80, 0x80), 1.0, 0),
+			grabbing: Stroke::new(ThemeColor::rgb(0xff, 0x00, 0xff), 4.0, 0),
+			guide: Stroke::new(white, 2.0, 0),
+			target: Stroke::new(yellow, 2.0, 8),
+		}
+	}
+
+	fn path(name: &str) -> Option<PathBuf> {
+		Some(Settings::dir()?.join("themes").join(format!("{name}.json")))
+	}
+
+	/// A built-in theme or the user's own, falling back to `default` if it can't be read.
+	pub fn load(name: &str) -> Self {
+		match name {
+			"default" => return Theme::default(),
+			"colorblind" => return Theme::colorblind(),
+			"high_contrast" => return Theme::high_contrast(),
+			_ => {}
+		}
+		let Some(path) = Self::path(name) else {
+			return Theme::default();
+		};
+		match fs::read_to_string(&path) {
+			Ok(theme) => serde_json::from_str(&theme)
+				.inspect_err(|err| error!("invalid theme in {}: {err}", path.display()))
+				.unwrap_or_default(),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				error!("no theme named {name}, looked in {}", path.display());
+				Theme::default()
+			}
+			Err(err) => {
+				error!("unable to read {}: {err}", path.display());
+				Theme::default()
+			}
+		}
+	}
+
+	pub fn mode(&self, mode: Mode) -> Stroke {
+		match mode {
+			Mode::Reparent => self.reparent,
+			Mode::MonadoOffset => self.monado_offset,
+			Mode::Walk => self.walk,
+			Mode::Orbit => self.orbit,
+			Mode::Zone => self.zone,
+			Mode::Disabled => self.disabled,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use stardust_xr_fusion::drawable::{Line, LinePoint};
+
+	use super::{Stroke, Theme, ThemeColor};
+	use crate::locomotion::Mode;
+
+	#[test]
+	fn colors_read_and_write_as_hex() {
+		let color = ThemeColor::try_from("#e69f00".to_string()).unwrap();
+		assert_eq!(color, ThemeColor::rgb(0xe6, 0x9f, 0x00));
+		assert_eq!(color.to_string(), "#e69f00");
+		let faded = ThemeColor::try_from("ffffff80".to_string()).unwrap();
+		assert_eq!(faded.to_string(), "#ffffff80");
+		assert!(ThemeColor::try_from("#fff".to_string()).is_err());
+		assert!(ThemeColor::try_from("#gggggg".to_string()).is_err());
+	}
+
+	#[test]
+	fn user_themes_fill_in_from_default() {
+		let theme: Theme = serde_json::from_str(
+			r##"{ "walk": { "color": "#ff0000", "dashes": 3 }, "grabbing": { "color": "#0000ff", "thickness": 3.0 } }"##,
+		)
+		.unwrap();
+		assert_eq!(theme.walk.color, ThemeColor::rgb(0xff, 0x00, 0x00));
+		assert_eq!((theme.walk.thickness, theme.walk.dashes), (1.0, 3));
+		assert_eq!(theme.grabbing.thickness, 3.0);
+		assert_eq!(theme.orbit, Theme::default().orbit);
+	}
+
+	#[test]
+	fn every_state_differs_by_more_than_color() {
+		for theme in [
+			Theme::default(),
+			Theme::colorblind(),
+			Theme::high_contrast(),
+		] {
+			let strokes = [
+				Mode::Reparent,
+				Mode::MonadoOffset,
+				Mode::Walk,
+				Mode::Orbit,
+				Mode::Zone,
+			]
+			.map(|mode| theme.mode(mode))
+			.into_iter()
+			.chain([theme.grabbing]);
+			let cues = strokes
+				.map(|stroke| (stroke.thickness.to_bits(), stroke.dashes))
+				.collect::<Vec<_>>();
+			for (i, cue) in cues.iter().enumerate() {
+				assert!(!cues[i + 1..].contains(cue), "{theme:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn dashes_break_up_the_line() {
+		let point = |x: f32| LinePoint {
+			point: [x, 0.0, 0.0].into(),
+			thickness: 1.0,
+			color: ThemeColor([1.0; 4]).linear(),
+		};
+		let line = Line {
+			points: (0..=16).map(|x| point(x as f32)).collect(),
+			cyclic: false,
+		};
+		let stroke = |dashes| Stroke::new(ThemeColor([1.0; 4]), 2.0, dashes);
+		let solid = stroke(0).draw(line.clone(), 0.5);
+		assert_eq!(solid.len(), 1);
+		assert_eq!(solid[0].points[0].thickness, 1.0);
+
+		let dashed = stroke(4).draw(line, 0.5);
+		assert_eq!(dashed.len(), 4);
+		assert!(
+			dashed
+				.iter()
+				.all(|dash| dash.points.len() == 3 && !dash.cyclic)
+		);
+	}
+}
@@ -0,0 +1,129 @@
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::{
+	drawable::{Line, Lines, LinesAspect as _},
+	fields::{Field, Shape},
+	node::NodeResult,
+	spatial::{SpatialRefAspect, Transform},
+};
+use stardust_xr_molecules::lines::{LineExt as _, circle};
+
+use crate::theme::Stroke;
+
+/// Shape of the zone placed in zone mode, centered where it's placed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneShape {
+	Box { half_size: [f32; 3] },
+	Sphere { radius: f32 },
+}
+impl Default for ZoneShape {
+	fn default() -> Self {
+		ZoneShape::Sphere { radius: 0.5 }
+	}
+}
+impl ZoneShape {
+	fn field_shape(&self) -> Shape {
+		match *self {
+			ZoneShape::Box { half_size } => Shape::Box((Vec3::from(half_size) * 2.0).into()),
+			ZoneShape::Sphere { radius } => Shape::Sphere(radius),
+		}
+	}
+
+	/// Wireframe of the shape, so the user can see what's inside.
+	pub fn outline(&self, stroke: Stroke) -> Vec<Line> {
+		match *self {
+			ZoneShape::Sphere { radius } => [
+				Quat::IDENTITY,
+				Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+				Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+			]
+			.into_iter()
+			.flat_map(|rotation| {
+				stroke.draw(
+					circle(64, 0.0, radius).transform(Mat4::from_quat(rotation)),
+					0.002,
+				)
+			})
+			.collect(),
+			ZoneShape::Box { half_size } => {
+				let half = Vec3::from(half_size);
+				let corner = |x: f32, y: f32, z: f32| half * Vec3::new(x, y, z);
+				// a square at either end along z, plus the 4 edges joining them
+				[-1.0, 1.0]
+					.into_iter()
+					.map(|z| {
+						vec![
+							corner(-1.0, -1.0, z),
+							corner(1.0, -1.0, z),
+							corner(1.0, 1.0, z),
+							corner(-1.0, 1.0, z),
+						]
+					})
+					.map(|points| (points, true))
+					.chain(
+						[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+							.into_iter()
+							.map(|(x, y)| (vec![corner(x, y, -1.0), corner(x, y, 1.0)], false)),
+					)
+					.flat_map(|(points, cyclic)| stroke.polyline(points, cyclic, 0.002))
+					.collect()
+			}
+		}
+	}
+}
+
+/// The field deciding which objects a zone moves, and its outline.
+pub struct Zone {
+	pub field: Field,
+	outline: Lines,
+	shape: ZoneShape,
+	stroke: Stroke,
+}
+impl Zone {
+	/// Created hidden, centered on `parent`.
+	pub fn create(
+		parent: &impl SpatialRefAspect,
+		shape: ZoneShape,
+		stroke: Stroke,
+	) -> NodeResult<Self> {
+		Ok(Zone {
+			field: Field::create(parent, Transform::identity(), shape.field_shape())?,
+			outline: Lines::create(parent, Transform::identity(), &[])?,
+			shape,
+			stroke,
+		})
+	}
+
+	pub fn set_visible(&self, visible: bool) -> NodeResult<()> {
+		match visible {
+			true => self.outline.set_lines(&self.shape.outline(self.stroke)),
+			false => self.outline.set_lines(&[]),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ZoneShape;
+	use crate::theme::Theme;
+
+	#[test]
+	fn box_outline_has_every_edge() {
+		let outline = ZoneShape::Box {
+			half_size: [0.5, 0.25, 1.0],
+		}
+		.outline(Theme::default().guide);
+		let edges = outline
+			.iter()
+			.map(|line| match line.cyclic {
+				true => line.points.len(),
+				false => line.points.len() - 1,
+			})
+			.sum::<usize>();
+		assert_eq!(edges, 12);
+		assert!(outline.iter().flat_map(|line| &line.points).all(|point| {
+			point.point.x.abs() == 0.5 && point.point.y.abs() == 0.25 && point.point.z.abs() == 1.0
+		}));
+	}
+}